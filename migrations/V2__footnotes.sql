@@ -0,0 +1,4 @@
+ALTER TABLE markdowns ADD COLUMN footnotes TEXT NOT NULL DEFAULT '[]';
+
+-- Pages cached before footnotes were parsed would come back without any, so parse them afresh.
+DELETE FROM markdowns;
@@ -0,0 +1,4 @@
+ALTER TABLE markdowns ADD COLUMN summary TEXT NOT NULL DEFAULT '[]';
+
+-- Pages cached before summaries were split off would come back without one, so parse them afresh.
+DELETE FROM markdowns;
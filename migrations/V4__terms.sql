@@ -0,0 +1,13 @@
+CREATE TABLE terms (
+    url      TEXT NOT NULL,
+    taxonomy TEXT NOT NULL,
+    slug     TEXT NOT NULL,
+    term     TEXT NOT NULL,
+    PRIMARY KEY (url, taxonomy, slug)
+);
+
+CREATE INDEX terms_by_slug ON terms (taxonomy, slug);
+
+-- Pages cached before terms were indexed have none filed, nor their `extra` frontmatter, so parse
+-- them afresh.
+DELETE FROM markdowns;
@@ -0,0 +1,3 @@
+-- Cached frontmatter had its times converted to UTC, so have every page parsed afresh.
+DELETE FROM markdowns;
+DELETE FROM terms;
@@ -0,0 +1,4 @@
+ALTER TABLE markdowns ADD COLUMN links TEXT NOT NULL DEFAULT '[]';
+
+-- Pages cached before their links were recorded would never be checked, so parse them afresh.
+DELETE FROM markdowns;
@@ -122,22 +122,30 @@ pub fn render_css<P: AsRef<Path>>(
 
     // TODO ultimately everything from here on out should be pretty similar to `collect` below.
 
-    let css = css.as_bytes();
-
     // TODO wonder if something else should be used as the cache for this particular file...
     // this requires having iterated through all the files, so you can't do the CSS generation in
     // parallel. meanwhile we'd like to know the tailwind.css file name before we start rendering
     // templates, so we can programmatically list the correct name.
-    let hash = utils::stringify_hash(utils::hash(css));
+    Ok(write_generated_asset(base_name, css.as_bytes(), out_dir))
+}
+
+/// Write a generated asset to the static directory of the output directory under a cache busted
+/// name, which is returned.
+pub fn write_generated_asset<P: AsRef<Path>>(
+    base_name: &str,
+    contents: &[u8],
+    out_dir: P,
+) -> String {
+    let hash = utils::stringify_hash(utils::hash(contents));
     let filename = make_cache_busted_name(Path::new(base_name), &hash);
 
     let static_dir = out_dir.as_ref().join("static");
     std::fs::create_dir_all(&static_dir).unwrap();
     let out_path = static_dir.join(&filename);
-    let mut css_file = File::create(out_path).unwrap();
-    css_file.write_all(css).unwrap();
+    let mut file = File::create(out_path).unwrap();
+    file.write_all(contents).unwrap();
 
-    Ok(filename.to_str().unwrap().to_string())
+    filename.to_str().unwrap().to_string()
 }
 
 pub async fn collect<C: Deref<Target = Config>, P: AsRef<Path>>(
@@ -1,5 +1,6 @@
 use futures::stream::unfold;
 use futures::Stream;
+use rusqlite::types::Value;
 use tokio_rusqlite::*;
 
 use crate::common::*;
@@ -93,7 +94,7 @@ pub async fn restore_cached(
     let query_result = match site_entry.get_page_type() {
         PageType::Markdown => restore_cached_markdown(conn, site_entry, hash)
             .await?
-            .map(|(md, rendered)| (CachedPageData::Markdown(hash, md, rendered))),
+            .map(|(md, rendered)| (CachedPageData::Markdown(hash, Box::new(md), rendered))),
         PageType::Liquid => restore_cached_page(conn, site_entry, hash)
             .await?
             .map(|rendered| (CachedPageData::Liquid(hash, rendered))),
@@ -113,7 +114,8 @@ async fn restore_cached_markdown(
     let url_path = site_entry.url_path.clone();
     conn.call(move |conn| {
         conn.query_row(
-            "SELECT frontmatter, blocks, rendered FROM markdowns WHERE url=:url AND hash=:hash",
+            "SELECT frontmatter, blocks, summary, footnotes, links, rendered FROM markdowns
+             WHERE url=:url AND hash=:hash",
             named_params! {
                 ":url": url_path,
                 ":hash": utils::stringify_hash(hash),
@@ -121,11 +123,17 @@ async fn restore_cached_markdown(
             |row| {
                 let frontmatter: String = row.get(0)?;
                 let blocks: String = row.get(1)?;
+                let summary: String = row.get(2)?;
+                let footnotes: String = row.get(3)?;
+                let links: String = row.get(4)?;
                 let markdown = Markdown {
                     frontmatter: serde_yaml::from_str(&frontmatter).unwrap(),
                     blocks: serde_yaml::from_str(&blocks).unwrap(),
+                    summary: serde_yaml::from_str(&summary).unwrap(),
+                    footnotes: serde_yaml::from_str(&footnotes).unwrap(),
+                    links: serde_yaml::from_str(&links).unwrap(),
                 };
-                let rendered: String = row.get(2)?;
+                let rendered: String = row.get(5)?;
                 Ok((markdown, rendered))
             },
         )
@@ -183,16 +191,43 @@ async fn cache_markdown(
 ) -> anyhow::Result<()> {
     let frontmatter = serde_yaml::to_string(&markdown.frontmatter).unwrap();
     let blocks = serde_yaml::to_string(&markdown.blocks).unwrap();
+    let summary = serde_yaml::to_string(&markdown.summary).unwrap();
+    let footnotes = serde_yaml::to_string(&markdown.footnotes).unwrap();
+    let links = serde_yaml::to_string(&markdown.links).unwrap();
     let timestamp = markdown.frontmatter.timestamp.timestamp();
+    let terms = Taxonomy::ALL
+        .iter()
+        .flat_map(|taxonomy| {
+            markdown
+                .frontmatter
+                .terms(*taxonomy)
+                .iter()
+                .map(|term| (taxonomy.name(), utils::slugify(term), term.clone()))
+        })
+        .collect::<Vec<_>>();
     conn.call(move |conn| {
         conn.execute(
-            "INSERT INTO markdowns (url, parent_url, hash, timestamp, frontmatter, blocks, rendered)
-             VALUES (:url, :parent_url, :hash, :timestamp, :frontmatter, :blocks, :rendered)
+            "INSERT INTO markdowns
+                (
+                    url, parent_url, hash, timestamp, frontmatter, blocks, summary, footnotes,
+                    links, rendered
+                )
+             VALUES
+                (
+                    :url, :parent_url, :hash, :timestamp, :frontmatter, :blocks, :summary,
+                    :footnotes, :links, :rendered
+                )
              ON CONFLICT(url) DO
                  UPDATE
                  SET
                     hash = excluded.hash,
-                    frontmatter = excluded.frontmatter
+                    timestamp = excluded.timestamp,
+                    frontmatter = excluded.frontmatter,
+                    blocks = excluded.blocks,
+                    summary = excluded.summary,
+                    footnotes = excluded.footnotes,
+                    links = excluded.links,
+                    rendered = excluded.rendered
             ",
             named_params! {
                 ":url": site_entry.url_path,
@@ -201,9 +236,32 @@ async fn cache_markdown(
                 ":timestamp": timestamp,
                 ":frontmatter": &frontmatter,
                 ":blocks": &blocks,
+                ":summary": &summary,
+                ":footnotes": &footnotes,
+                ":links": &links,
                 ":rendered": rendered,
             },
         )?;
+        conn.execute("DELETE FROM terms WHERE url = ?", [&site_entry.url_path])?;
+        for (taxonomy, slug, term) in terms {
+            // Terms written differently but with the same slug only count once.
+            conn.execute(
+                "INSERT OR IGNORE INTO terms (url, taxonomy, slug, term) VALUES (?, ?, ?, ?)",
+                [&site_entry.url_path, taxonomy, &slug, &term],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(Into::into)
+}
+
+/// Forget the cached `Markdown` at `url_path`, so it no longer turns up in listings.
+pub async fn delete_markdown(conn: &Connection, url_path: &str) -> anyhow::Result<()> {
+    let url_path = url_path.to_string();
+    conn.call(move |conn| {
+        conn.execute("DELETE FROM markdowns WHERE url = ?", [&url_path])?;
+        conn.execute("DELETE FROM terms WHERE url = ?", [&url_path])?;
         Ok(())
     })
     .await
@@ -223,7 +281,8 @@ async fn cache_page(
              ON CONFLICT(url) DO
                  UPDATE
                  SET
-                    hash = excluded.hash
+                    hash = excluded.hash,
+                    rendered = excluded.rendered
             ",
             named_params! {
                 ":url": site_entry.url_path,
@@ -237,12 +296,42 @@ async fn cache_page(
     Ok(())
 }
 
-pub async fn get_page_group_count(conn: &Connection, parent_url: &str) -> anyhow::Result<u8> {
-    let parent_url = parent_url.to_string();
+/// A group of cached `Markdown`s that get listed together.
+#[derive(Clone, Copy, Debug)]
+pub enum ListingGroup<'a> {
+    // The pages directly under the parent url.
+    Children(&'a str),
+    // The pages filed under the term with the slug.
+    Term(Taxonomy, &'a str),
+}
+
+impl ListingGroup<'_> {
+    /// The `FROM` & `WHERE` clauses selecting the `markdowns` of the group, along with their
+    /// parameters.
+    fn query(&self) -> (&'static str, Vec<Value>) {
+        match self {
+            ListingGroup::Children(parent_url) => (
+                "FROM markdowns WHERE parent_url = ?",
+                vec![Value::Text(parent_url.to_string())],
+            ),
+            ListingGroup::Term(taxonomy, slug) => (
+                "FROM markdowns JOIN terms ON terms.url = markdowns.url
+                 WHERE terms.taxonomy = ? AND terms.slug = ?",
+                vec![
+                    Value::Text(taxonomy.name().to_string()),
+                    Value::Text(slug.to_string()),
+                ],
+            ),
+        }
+    }
+}
+
+pub async fn get_listing_count(conn: &Connection, group: ListingGroup<'_>) -> anyhow::Result<u8> {
+    let (from, params) = group.query();
     conn.call(move |conn| {
         conn.query_row(
-            "SELECT COUNT(*) FROM markdowns WHERE parent_url = ?",
-            [parent_url],
+            &format!("SELECT COUNT(*) {}", from),
+            params_from_iter(params),
             |row| row.get(0),
         )
         .map_err(Into::into)
@@ -251,9 +340,37 @@ pub async fn get_page_group_count(conn: &Connection, parent_url: &str) -> anyhow
     .map_err(Into::into)
 }
 
+/// Every term of the `taxonomy` that some page is filed under, in order.
+pub async fn get_terms(conn: &Connection, taxonomy: Taxonomy) -> anyhow::Result<Vec<Term>> {
+    conn.call(move |conn| {
+        let mut stmt = conn.prepare(
+            "
+            SELECT slug, MIN(term), COUNT(*)
+            FROM terms
+            WHERE taxonomy = ?
+            GROUP BY slug
+            ORDER BY slug
+            ",
+        )?;
+        let terms = stmt
+            .query_map([taxonomy.name()], |row| {
+                Ok(Term {
+                    taxonomy,
+                    slug: row.get(0)?,
+                    name: row.get(1)?,
+                    count: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(terms)
+    })
+    .await
+    .map_err(Into::into)
+}
+
 struct MarkdownIteratorState<'a> {
     conn: Connection, // Consider Arc<Mutex<Connection>> for shared access
-    parent_url: &'a str,
+    group: ListingGroup<'a>,
     limit: u8,
     offset: u8,
 }
@@ -263,35 +380,44 @@ async fn fetch_next_batch(
 ) -> Option<(Vec<(Markdown, String)>, MarkdownIteratorState<'_>)> {
     let MarkdownIteratorState {
         conn,
-        parent_url,
+        group,
         limit,
         offset,
     } = state;
 
     // Execute the query in a blocking task
     let results: Vec<(Markdown, String)> = {
-        let parent_url = parent_url.to_string();
+        let (from, mut params) = group.query();
+        params.extend([Value::Integer(limit.into()), Value::Integer(offset.into())]);
         conn.call(move |conn| {
-            let mut stmt = conn.prepare(
+            let mut stmt = conn.prepare(&format!(
                 "
-                SELECT frontmatter, blocks, url
-                FROM markdowns
-                WHERE parent_url = ?
-                ORDER BY timestamp
+                SELECT
+                    markdowns.frontmatter, markdowns.blocks, markdowns.summary,
+                    markdowns.footnotes, markdowns.url
+                {}
+                ORDER BY markdowns.timestamp
                 LIMIT ?
                 OFFSET ?
                 ",
-            )?;
+                from
+            ))?;
 
             let results = stmt
-                .query_map(params![parent_url, limit, offset], |row| {
+                .query_map(params_from_iter(params), |row| {
                     let frontmatter: String = row.get(0)?;
                     let blocks: String = row.get(1)?;
-                    let url: String = row.get(2)?;
+                    let summary: String = row.get(2)?;
+                    let footnotes: String = row.get(3)?;
+                    let url: String = row.get(4)?;
 
                     let markdown = Markdown {
                         frontmatter: serde_yaml::from_str(&frontmatter).unwrap(),
                         blocks: serde_yaml::from_str(&blocks).unwrap(),
+                        summary: serde_yaml::from_str(&summary).unwrap(),
+                        footnotes: serde_yaml::from_str(&footnotes).unwrap(),
+                        // Only of use in restoring the page itself.
+                        links: vec![],
                     };
                     Ok((markdown, url))
                 })?
@@ -309,7 +435,7 @@ async fn fetch_next_batch(
             results,
             MarkdownIteratorState {
                 conn,
-                parent_url,
+                group,
                 limit,
                 offset: offset + limit,
             },
@@ -319,13 +445,13 @@ async fn fetch_next_batch(
 
 pub fn markdown_stream(
     conn: Connection,
-    parent_url: &str,
+    group: ListingGroup<'_>,
     limit: u8,
 ) -> impl Stream<Item = Vec<(Markdown, String)>> + '_ {
     unfold(
         MarkdownIteratorState {
             conn,
-            parent_url,
+            group,
             limit,
             offset: 0,
         },
@@ -27,11 +27,17 @@ enum Commands {
     Gen {
         #[arg(long, default_value_t = false)]
         no_cache: bool,
+        /// Include drafts and pages scheduled for later.
+        #[arg(long, default_value_t = false)]
+        drafts: bool,
     },
     /// Run a server.
     Server {
         #[arg(long, default_value_t = false)]
         dev: bool,
+        /// Include drafts and pages scheduled for later.
+        #[arg(long, default_value_t = false, requires = "dev")]
+        drafts: bool,
     },
 }
 
@@ -70,13 +76,15 @@ async fn main() {
             }
         },
         // TODO propagate the actual error
-        Commands::Gen { no_cache } => {
-            let config = config.with_no_cache(*no_cache);
+        Commands::Gen { no_cache, drafts } => {
+            let config = config.with_no_cache(*no_cache).with_drafts(*drafts);
             generate(Arc::new(config))
                 .await
                 .expect("Error generating site");
         }
         // TODO server should be an optional feature
-        Commands::Server { dev } => run_server(Arc::new(config), *dev).await,
+        Commands::Server { dev, drafts } => {
+            run_server(Arc::new(config.with_drafts(*drafts)), *dev).await
+        }
     }
 }
@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::ops::Deref;
+use std::process::Command;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use camino::{Utf8Path, Utf8PathBuf};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Local, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
+use crate::parsers::markdown::{self, MarkdownError};
+use crate::parsers::wikilinks::ResolvedLink;
 use crate::utils;
 use crate::utils::slugify;
 
@@ -14,6 +20,11 @@ lazy_static! {
         layouts: vec!["primary".to_string()],
         block_rules: None,
         listing: None,
+        parser: MarkdownBackend::Native,
+        smart_typography: false,
+        highlight: None,
+        taxonomies: HashMap::new(),
+        timezone: None,
     });
 }
 
@@ -42,6 +53,43 @@ impl Deref for FileContent {
     }
 }
 
+/// When each file was last committed to, in the timezone of the committer, by path relative to
+/// the directory the git log was taken in.
+pub type CommitTimes = HashMap<Utf8PathBuf, DateTime<FixedOffset>>;
+
+/// Go through the git log of `dir` just the once, to find when each file in it was last
+/// committed to. Comes up empty outside of any repository.
+pub fn collect_commit_times(dir: &Utf8Path) -> CommitTimes {
+    let mut commit_times = CommitTimes::new();
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--name-only",
+            "--relative",
+        ])
+        .args(["--format=%cI", "--", "."])
+        .current_dir(dir)
+        .output();
+    let Ok(output) = output else {
+        return commit_times;
+    };
+    // Each commit is its time, followed by the files it touched.
+    let mut time = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Ok(parsed) = DateTime::parse_from_rfc3339(line) {
+            time = Some(parsed);
+        } else if let Some(time) = time
+            && !line.is_empty()
+        {
+            // Newest commits come first, so only the first time seen counts.
+            commit_times.entry(Utf8PathBuf::from(line)).or_insert(time);
+        }
+    }
+    commit_times
+}
+
 #[derive(Debug)]
 pub struct ContentFile {
     // Absolute path of the file.
@@ -72,6 +120,25 @@ impl ContentFile {
         self.rel_path.parent().unwrap().to_owned()
     }
 
+    /// Come up with a timestamp for a page that doesn't specify one: a date prefix of the file
+    /// name, or else the date of its last git commit (as found in the `commit_times` of the pages
+    /// directory), or else its modification time.
+    pub fn infer_timestamp(
+        &self,
+        commit_times: &CommitTimes,
+    ) -> anyhow::Result<(DateTime<FixedOffset>, TimestampSource)> {
+        if let Some((date, _)) = self.rel_path.file_stem().and_then(utils::split_date_prefix) {
+            let timestamp = date.and_time(NaiveTime::MIN).and_utc().fixed_offset();
+            return Ok((timestamp, TimestampSource::Filename));
+        }
+        if let Some(timestamp) = commit_times.get(&self.rel_path) {
+            return Ok((*timestamp, TimestampSource::Git));
+        }
+        let modified = std::fs::metadata(&self.abs_path)?.modified()?;
+        let modified: DateTime<Local> = modified.into();
+        Ok((modified.fixed_offset(), TimestampSource::Modified))
+    }
+
     pub async fn initialize_file_content(&mut self) -> anyhow::Result<()> {
         if self.content.is_none() {
             let contents = tokio::fs::read(&self.abs_path).await;
@@ -107,6 +174,8 @@ pub struct SiteEntry {
     pub out_path: Utf8PathBuf,
     // Relative url path.
     pub url_path: String,
+    // The frontmatter of a markdown page & the offset of the body after it, once read.
+    pub frontmatter: Option<Result<(FrontMatter, usize), MarkdownError>>,
 }
 
 impl SiteEntry {
@@ -117,9 +186,14 @@ impl SiteEntry {
         ) {
             let file = ContentFile::new(pages_dir, abs_path).await?;
             // let rel_path = abs_path.strip_prefix(pages_dir)?.to_owned();
-            let mut out_path = file
-                .rel_path
-                .with_extension("")
+            let mut rel_path = file.rel_path.with_extension("");
+            // Date prefixes of posts only go toward the timestamp, not the url.
+            if file.rel_path.extension() == Some("md")
+                && let Some((_, rest)) = rel_path.file_name().and_then(utils::split_date_prefix)
+            {
+                rel_path = rel_path.with_file_name(rest);
+            }
+            let mut out_path = rel_path
                 .components()
                 .map(|c| slugify(c.as_os_str().to_str().unwrap()))
                 .collect::<Vec<_>>()
@@ -141,12 +215,32 @@ impl SiteEntry {
                 file,
                 out_path,
                 url_path,
+                frontmatter: None,
             })
         } else {
             anyhow::bail!("Invalid file type")
         }
     }
 
+    /// Parse the frontmatter of a markdown page from its loaded content, so that it only gets
+    /// parsed the once, whether to filter, index or render the page.
+    pub fn read_frontmatter(&mut self) {
+        if matches!(self.get_page_type(), PageType::Markdown)
+            && let Some(content) = self.file.content.as_ref()
+        {
+            self.frontmatter = Some(markdown::parse_frontmatter(content));
+        }
+    }
+
+    /// The frontmatter of a markdown page, unless it's yet to be read or is broken. Broken
+    /// frontmatter gets reported when the page itself is rendered.
+    pub fn valid_frontmatter(&self) -> Option<&FrontMatter> {
+        match &self.frontmatter {
+            Some(Ok((frontmatter, _))) => Some(frontmatter),
+            _ => None,
+        }
+    }
+
     /// Returns the type of the file.
     pub fn get_page_type(&self) -> PageType {
         match self.file.rel_path.extension() {
@@ -189,15 +283,19 @@ pub struct SiteNode {
 
 #[derive(Debug)]
 pub enum PageData {
-    Markdown(Markdown),
+    Markdown(Box<Markdown>),
     Liquid(FileContent),
     Html(FileContent),
     Listing(String, Vec<(Markdown, String)>, PageIndex),
+    // The pages filed under a single term, like a tag.
+    TermListing(Term, Vec<(Markdown, String)>, PageIndex),
+    // Every term of a taxonomy.
+    Terms(Taxonomy, Vec<Term>),
 }
 
 #[derive(Debug)]
 pub enum CachedPageData {
-    Markdown(u64, Markdown, String),
+    Markdown(u64, Box<Markdown>, String),
     Liquid(u64, String),
     Html(u64, String),
 }
@@ -216,6 +314,13 @@ impl CachedPageData {
 pub struct Markdown {
     pub frontmatter: FrontMatter,
     pub blocks: Vec<Block>,
+    // The leading blocks of the post (also still in `blocks`), or the `summary` frontmatter.
+    pub summary: Vec<Block>,
+    // Also appended to `blocks` as a `footnotes` block, but kept here for layouts that want
+    // to place them elsewhere.
+    pub footnotes: Vec<Block>,
+    // Where the wiki-style links of the page led, to tell whether a cached render still holds.
+    pub links: Vec<ResolvedLink>,
 }
 
 pub type BlockRules = HashMap<String, String>;
@@ -226,12 +331,170 @@ pub struct RenderRules {
     #[serde(rename = "blocks")]
     pub block_rules: Option<BlockRules>,
     pub listing: Option<ListingRules>,
+    #[serde(default)]
+    pub parser: MarkdownBackend,
+    // Curly quotes, dashes & ellipses in place of their plain ASCII approximations.
+    #[serde(default)]
+    pub smart_typography: bool,
+    pub highlight: Option<HighlightRules>,
+    // Listings of pages by their tags or categories. These span the whole site, so only the
+    // layouts & page size come from the rules here.
+    #[serde(default)]
+    pub taxonomies: HashMap<Taxonomy, TaxonomyRules>,
+    // What the `date` filter shows times in, rather than the offsets they were written with.
+    pub timezone: Option<Timezone>,
+}
+
+/// A timezone to show times in: either a name like `Europe/Berlin`, or an offset like `+02:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Timezone {
+    Named(chrono_tz::Tz),
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    /// The same instant as `time`, on the clock of the timezone.
+    pub fn convert(&self, time: &DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Named(tz) => time.with_timezone(tz).fixed_offset(),
+            Timezone::Fixed(offset) => time.with_timezone(offset),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Timezone::Fixed)
+            .or_else(|_| s.parse().map(Timezone::Named))
+            .map_err(|_| format!("unknown timezone `{}`", s))
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Timezone> for String {
+    fn from(timezone: Timezone) -> Self {
+        timezone.to_string()
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Named(tz) => write!(f, "{}", tz.name()),
+            Timezone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+/// A way of filing pages under terms, as listed in their frontmatter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Taxonomy {
+    Tags,
+    Categories,
+}
+
+impl Taxonomy {
+    pub const ALL: [Taxonomy; 2] = [Taxonomy::Tags, Taxonomy::Categories];
+
+    /// The name of the taxonomy, both as the frontmatter key and as the url path.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Taxonomy::Tags => "tags",
+            Taxonomy::Categories => "categories",
+        }
+    }
+
+    /// The url path of the listing of the pages filed under the `slug`.
+    pub fn term_url_path(&self, slug: &str) -> String {
+        format!("{}/{}", self.name(), slug)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaxonomyRules {
+    // Layouts of the listing of the pages filed under each term.
+    pub layouts: Vec<String>,
+    // Layouts of the index of every term.
+    pub index_layouts: Vec<String>,
+    pub page_size: Option<u8>,
+}
+
+/// A term of a taxonomy, along with how many pages are filed under it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    pub taxonomy: Taxonomy,
+    // The term as written in the frontmatter.
+    pub name: String,
+    pub slug: String,
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRules {
+    // One of `syntect`'s default themes, like `InspiredGitHub` or `base16-ocean.dark`.
+    pub theme: String,
+    #[serde(default)]
+    pub style: HighlightStyle,
+}
+
+/// How highlighted code gets its colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    // Classes, styled by the theme's stylesheet, available as the `highlight-<theme>.css` asset.
+    #[default]
+    Classes,
+    // Inline `style` attributes, needing no stylesheet.
+    Inline,
+}
+
+/// Which parser to run over the markdown pages of a directory.
+/// Both produce the same `Block` tree, so block rules & templates work the same either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkdownBackend {
+    // Our own parser, covering the subset of markdown we actually use.
+    #[default]
+    Native,
+    // Full CommonMark plus GFM extensions, for content imported from other generators.
+    CommonMark,
 }
 
 impl RenderRules {
     pub fn should_render_listing(&self) -> bool {
         self.listing.is_some()
     }
+
+    /// The rules for a page with the `frontmatter`, which may override the layouts and some of
+    /// the block rules of its directory.
+    pub fn for_page(self: &Arc<Self>, frontmatter: &FrontMatter) -> Arc<Self> {
+        if frontmatter.layouts.is_none() && frontmatter.block_rules.is_none() {
+            return self.clone();
+        }
+        let mut rules = self.as_ref().clone();
+        if let Some(layouts) = &frontmatter.layouts {
+            rules.layouts = layouts.clone();
+        }
+        if let Some(block_rules) = &frontmatter.block_rules {
+            rules
+                .block_rules
+                .get_or_insert_with(Default::default)
+                .extend(block_rules.clone());
+        }
+        Arc::new(rules)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -252,6 +515,10 @@ pub struct Block {
 // TODO "token" isn't really a great name for these.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Token {
+    /// Text that's HTML already, to be written out as is, whichever parser it came from: special
+    /// characters are escaped (entities are kept), and any raw HTML is in an `html` block.
+    /// The exceptions are `pre` & `math` blocks, whose literal is their source exactly as written,
+    /// for the template or highlighter to deal with. `meta` values are always raw text.
     Literal(String),
     Block(Block),
 }
@@ -260,13 +527,80 @@ pub enum Token {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FrontMatter {
     pub title: String,
-    pub timestamp: DateTime<Utc>,
+    // Times keep the offset they were written with, for display. Sorting goes by the instant.
+    pub timestamp: DateTime<FixedOffset>,
+    // Where the `timestamp` came from, since it need not be given explicitly.
+    #[serde(default)]
+    pub timestamp_source: TimestampSource,
+    // When the page was last revised, if it says so.
+    #[serde(default)]
+    pub updated: Option<DateTime<FixedOffset>>,
     pub slug: String,
-    // TODO any other arbitrary KVs?
+    // Markdown to use as the summary in place of the leading blocks of the post.
+    #[serde(default)]
+    pub summary: Option<String>,
+    // Layouts & block rules of the page itself, in place of those of its directory.
+    #[serde(default)]
+    pub layouts: Option<Vec<String>>,
+    #[serde(default, rename = "blocks")]
+    pub block_rules: Option<BlockRules>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    // Former urls of the page, which redirect to where it lives now.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // Drafts are only generated when previewing.
+    #[serde(default)]
+    pub draft: bool,
+    // Until when to hold off on publishing the page, as if it were a draft.
+    #[serde(default)]
+    pub publish_at: Option<DateTime<FixedOffset>>,
+    // Every other key, e.g. `description` or `author`, passed through to templates as is.
+    #[serde(default)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    // Given as `timestamp` in the frontmatter.
+    #[default]
+    Frontmatter,
+    // A `YYYY-MM-DD-` prefix of the file name, e.g. `2023-11-11-my-post.md`.
+    Filename,
+    // The date of the last git commit of the file.
+    Git,
+    // The modification time of the file.
+    Modified,
+    // Not in the frontmatter, and yet to be inferred from the file.
+    Missing,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum FrontMatterError {
+    #[error("missing `{0}`")]
+    MissingField(&'static str),
+    #[error("invalid {field}: {message}")]
+    InvalidField {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl FrontMatterError {
+    /// The name of the field at fault.
+    pub fn field(&self) -> &'static str {
+        match self {
+            FrontMatterError::MissingField(field) => field,
+            FrontMatterError::InvalidField { field, .. } => field,
+        }
+    }
 }
 
 impl TryFrom<Vec<(&str, &str)>> for FrontMatter {
-    type Error = &'static str;
+    type Error = FrontMatterError;
 
     fn try_from(kvs: Vec<(&str, &str)>) -> Result<Self, Self::Error> {
         let kvs = HashMap::from_iter(kvs);
@@ -275,25 +609,187 @@ impl TryFrom<Vec<(&str, &str)>> for FrontMatter {
 }
 
 impl TryFrom<HashMap<&str, &str>> for FrontMatter {
-    type Error = &'static str;
+    type Error = FrontMatterError;
 
     fn try_from(kv: HashMap<&str, &str>) -> Result<Self, Self::Error> {
-        let title = kv.get("title").ok_or("missing title")?;
-        let timestamp = kv
-            .get("timestamp")
-            .map(|s| DateTime::parse_from_rfc3339(s).map(|dt| dt.with_timezone(&Utc)))
-            .unwrap()
-            .unwrap();
-        let slug = kv
-            .get("slug")
-            .map_or_else(|| slugify(title), |s| s.to_string());
+        kv.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect::<serde_yaml::Mapping>()
+            .try_into()
+    }
+}
+
+impl TryFrom<serde_yaml::Mapping> for FrontMatter {
+    type Error = FrontMatterError;
+
+    fn try_from(mapping: serde_yaml::Mapping) -> Result<Self, Self::Error> {
+        let mut extra: BTreeMap<String, serde_yaml::Value> = mapping
+            .into_iter()
+            .map(|(k, v)| (yaml_key(&k), stringify_keys(v)))
+            .collect();
+        let mut take_text = |field: &'static str| {
+            extra
+                .remove(field)
+                .map(|value| {
+                    yaml_text(&value).ok_or_else(|| FrontMatterError::InvalidField {
+                        field,
+                        message: "expected a string".to_string(),
+                    })
+                })
+                .transpose()
+        };
+        let title = take_text("title")?.ok_or(FrontMatterError::MissingField("title"))?;
+        let parse_time = |field: &'static str, time: String| {
+            DateTime::parse_from_rfc3339(&time).map_err(|e| FrontMatterError::InvalidField {
+                field,
+                message: e.to_string(),
+            })
+        };
+        let (timestamp, timestamp_source) = match take_text("timestamp")? {
+            Some(timestamp) => (
+                parse_time("timestamp", timestamp)?,
+                TimestampSource::Frontmatter,
+            ),
+            // Only a placeholder until `ContentFile::infer_timestamp` comes up with something.
+            None => (
+                DateTime::UNIX_EPOCH.fixed_offset(),
+                TimestampSource::Missing,
+            ),
+        };
+        let updated = take_text("updated")?
+            .map(|updated| parse_time("updated", updated))
+            .transpose()?;
+        let slug = take_text("slug")?.unwrap_or_else(|| slugify(&title));
+        let summary = take_text("summary")?;
+        let publish_at = take_text("publish_at")?
+            .map(|publish_at| parse_time("publish_at", publish_at))
+            .transpose()?;
+        let mut take_list = |field: &'static str, message: &str| {
+            extra.remove(field).map_or(Ok(vec![]), |value| {
+                yaml_texts(&value).ok_or_else(|| FrontMatterError::InvalidField {
+                    field,
+                    message: message.to_string(),
+                })
+            })
+        };
+        let tags = take_list(Taxonomy::Tags.name(), "expected a list of terms")?;
+        let categories = take_list(Taxonomy::Categories.name(), "expected a list of terms")?;
+        let aliases = take_list("aliases", "expected a list of urls")?;
+        let layouts = extra
+            .remove("layouts")
+            .map(|value| {
+                let layouts = match value {
+                    serde_yaml::Value::Sequence(values) => values.iter().map(yaml_text).collect(),
+                    _ => None,
+                };
+                layouts.ok_or(FrontMatterError::InvalidField {
+                    field: "layouts",
+                    message: "expected a list of layout names".to_string(),
+                })
+            })
+            .transpose()?;
+        let block_rules = extra
+            .remove("blocks")
+            .map(|value| {
+                let block_rules = match value {
+                    serde_yaml::Value::Mapping(mapping) => mapping
+                        .iter()
+                        .map(|(kind, template)| Some((yaml_text(kind)?, yaml_text(template)?)))
+                        .collect(),
+                    _ => None,
+                };
+                block_rules.ok_or(FrontMatterError::InvalidField {
+                    field: "blocks",
+                    message: "expected block kinds mapped to templates".to_string(),
+                })
+            })
+            .transpose()?;
+        let draft = match extra.remove("draft") {
+            Some(draft) => draft.as_bool().ok_or(FrontMatterError::InvalidField {
+                field: "draft",
+                message: "expected true or false".to_string(),
+            })?,
+            None => false,
+        };
         Ok(FrontMatter {
-            title: title.to_string(),
+            title,
             timestamp,
+            timestamp_source,
+            updated,
             slug,
+            summary,
+            layouts,
+            block_rules,
+            tags,
+            categories,
+            aliases,
+            draft,
+            publish_at,
+            extra,
         })
     }
 }
 
+impl FrontMatter {
+    /// The terms the page is filed under in the `taxonomy`.
+    pub fn terms(&self, taxonomy: Taxonomy) -> &[String] {
+        match taxonomy {
+            Taxonomy::Tags => &self.tags,
+            Taxonomy::Categories => &self.categories,
+        }
+    }
+
+    /// Whether the page belongs on the live site as of `now`.
+    pub fn is_published(&self, now: DateTime<Utc>) -> bool {
+        let scheduled = self.publish_at.is_some_and(|publish_at| publish_at > now);
+        !(self.draft || scheduled)
+    }
+}
+
+/// The text of a scalar YAML `value`, which may have been written without quotes.
+fn yaml_text(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// The texts of a list of scalar YAML values. Lone values needn't be wrapped up in a list.
+fn yaml_texts(value: &serde_yaml::Value) -> Option<Vec<String>> {
+    match value {
+        serde_yaml::Value::Sequence(values) => values.iter().map(yaml_text).collect(),
+        value => yaml_text(value).map(|text| vec![text]),
+    }
+}
+
+/// Templates can only look things up by string, so every mapping key has to be one.
+fn yaml_key(key: &serde_yaml::Value) -> String {
+    yaml_text(key).unwrap_or_else(|| {
+        serde_yaml::to_string(key)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string()
+    })
+}
+
+fn stringify_keys(value: serde_yaml::Value) -> serde_yaml::Value {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => serde_yaml::Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(k, v)| (yaml_key(&k).into(), stringify_keys(v)))
+                .collect(),
+        ),
+        serde_yaml::Value::Sequence(values) => {
+            serde_yaml::Value::Sequence(values.into_iter().map(stringify_keys).collect())
+        }
+        // Local tags don't mean anything to templates, so just keep the value.
+        serde_yaml::Value::Tagged(tagged) => stringify_keys(tagged.value),
+        value => value,
+    }
+}
+
 /// Represents "page `i` of `n`".
 pub type PageIndex = (u8, u8);
@@ -12,6 +12,8 @@ pub struct Config {
     pub project_dir: Utf8PathBuf,
     pub outdir: Utf8PathBuf,
     pub no_cache: bool,
+    // Whether to generate drafts & scheduled pages too, for previewing.
+    pub drafts: bool,
 }
 
 impl Config {
@@ -28,6 +30,7 @@ impl Config {
             project_dir,
             outdir,
             no_cache: false,
+            drafts: false,
         })
     }
 
@@ -36,6 +39,11 @@ impl Config {
         self
     }
 
+    pub fn with_drafts(mut self, drafts: bool) -> Self {
+        self.drafts = drafts;
+        self
+    }
+
     pub fn layouts_dir(&self) -> Utf8PathBuf {
         self.project_dir.join("layouts")
     }
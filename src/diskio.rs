@@ -1,8 +1,9 @@
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
 
-use camino::Utf8PathBuf;
-use futures::{stream, Stream};
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::{stream, Stream, StreamExt};
 use ignore::Walk;
 use memmap2::Mmap;
 
@@ -93,6 +94,33 @@ pub async fn collect_site_nodes(config: Arc<Config>) -> Vec<SiteNode> {
     site_nodes
 }
 
+/// Copy any files within the pages directory that aren't pages themselves (e.g. images)
+/// over to the same relative path within `out_dir`, except for those in the (relative)
+/// `unpublished_dirs`.
+pub async fn copy_page_assets<P: AsRef<Path>>(
+    pages_dir: &Utf8Path,
+    out_dir: P,
+    unpublished_dirs: &HashSet<Utf8PathBuf>,
+) -> anyhow::Result<()> {
+    let paths: Vec<Utf8PathBuf> = walk(pages_dir, &None).collect().await;
+    for path in paths.iter().filter(|path| {
+        !matches!(path.extension(), Some("md") | Some("liquid") | Some("html"))
+            && path.file_name() != Some("rules.yaml")
+    }) {
+        let rel_path = path.strip_prefix(pages_dir)?;
+        if rel_path
+            .parent()
+            .is_some_and(|dir| unpublished_dirs.contains(dir))
+        {
+            continue;
+        }
+        let out_path = out_dir.as_ref().join(rel_path);
+        tokio::fs::create_dir_all(out_path.parent().unwrap()).await?;
+        tokio::fs::copy(path, out_path).await?;
+    }
+    Ok(())
+}
+
 // TODO this P, P2 thing is unseemly.
 // TODO should callers just take care of joining the path?
 // TODO probably also want to minify + compress
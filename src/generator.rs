@@ -1,10 +1,14 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::ops::Deref;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::UNIX_EPOCH;
 
 use anyhow::anyhow;
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
 use futures::future::join_all;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
@@ -13,9 +17,14 @@ use tokio_rusqlite::Connection;
 
 use crate::assets::AssetMap;
 use crate::common::*;
-use crate::parsers::markdown;
-use crate::utils::divide_round_up;
-use crate::{assets, cache, diskio, Config, Renderer};
+use crate::liquid::tags::shortcode_partial_name;
+use crate::parsers::markdown::{MarkdownError, SourceLocation};
+use crate::parsers::wikilinks::{self, SiteIndex};
+use crate::parsers::{commonmark, markdown, math, typography};
+use crate::redirects::{self, Alias};
+use crate::renderer::RenderResult;
+use crate::utils::{divide_round_up, slugify};
+use crate::{assets, cache, diskio, highlight, Config, Markdown, Renderer};
 
 async fn get_latest_modified(templates: &[ContentFile]) -> Option<u64> {
     let mut futures = FuturesUnordered::new();
@@ -72,17 +81,235 @@ async fn collect_templates(config: &Config) -> Vec<ContentFile> {
         .await
 }
 
-fn parse_page_data(site_entry: &SiteEntry, file_content: FileContent) -> anyhow::Result<PageData> {
+/// Whether the page of the `site_entry` belongs on the live site as of `now`.
+fn is_published(site_entry: &SiteEntry, now: DateTime<Utc>) -> bool {
+    match site_entry.valid_frontmatter() {
+        Some(frontmatter) => frontmatter.is_published(now),
+        None => true,
+    }
+}
+
+/// Leave out drafts & pages scheduled for later, along with anything cached of them.
+/// Returns the directories left with only unpublished pages, whose other files (like images)
+/// shouldn't go live either.
+async fn drop_unpublished(
+    conn: &Connection,
+    site_nodes: &mut [SiteNode],
+) -> anyhow::Result<HashSet<Utf8PathBuf>> {
+    let now = Utc::now();
+    let mut unpublished_dirs = HashSet::new();
+    for node in site_nodes.iter_mut() {
+        let (published, unpublished): (Vec<_>, Vec<_>) = std::mem::take(&mut node.site_entries)
+            .into_iter()
+            .partition(|site_entry| is_published(site_entry, now));
+        node.site_entries = published;
+        for site_entry in unpublished {
+            tracing::info!("skipping unpublished page {:?}", site_entry.file.rel_path);
+            cache::delete_markdown(conn, &site_entry.url_path).await?;
+            unpublished_dirs.insert(site_entry.file.rel_dir());
+        }
+    }
+    for site_entry in site_nodes.iter().flat_map(|node| node.site_entries.iter()) {
+        unpublished_dirs.remove(&site_entry.file.rel_dir());
+    }
+    Ok(unpublished_dirs)
+}
+
+/// Fill in the timestamps of pages that don't give one. The git log is only gone through if some
+/// page comes down to it, and then just the once.
+fn infer_timestamps(pages_dir: &Utf8Path, site_nodes: &mut [SiteNode]) -> anyhow::Result<()> {
+    let mut commit_times = None;
+    for site_entry in site_nodes
+        .iter_mut()
+        .flat_map(|node| &mut node.site_entries)
+    {
+        let Some(Ok((frontmatter, _))) = &mut site_entry.frontmatter else {
+            continue;
+        };
+        if frontmatter.timestamp_source != TimestampSource::Missing {
+            continue;
+        }
+        let commit_times = commit_times.get_or_insert_with(|| collect_commit_times(pages_dir));
+        (frontmatter.timestamp, frontmatter.timestamp_source) =
+            site_entry.file.infer_timestamp(commit_times)?;
+        tracing::debug!(
+            "{}: inferred timestamp from {:?}",
+            site_entry.file.rel_path,
+            frontmatter.timestamp_source
+        );
+    }
+    Ok(())
+}
+
+/// Where the index & term listings of each taxonomy will be written, given the terms the pages
+/// are filed under, along with a description of each.
+fn taxonomy_out_paths(
+    site_nodes: &[SiteNode],
+    taxonomy_rules: &HashMap<Taxonomy, Arc<RenderRules>>,
+) -> HashMap<Utf8PathBuf, String> {
+    let mut out_paths = HashMap::new();
+    for (taxonomy, render_rules) in taxonomy_rules {
+        let page_size = render_rules.taxonomies[taxonomy]
+            .page_size
+            .unwrap_or(DEFAULT_LISTING_PAGE_SIZE);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for frontmatter in site_nodes
+            .iter()
+            .flat_map(|node| node.site_entries.iter())
+            .filter_map(SiteEntry::valid_frontmatter)
+        {
+            for term in frontmatter.terms(*taxonomy) {
+                *counts.entry(slugify(term)).or_default() += 1;
+            }
+        }
+        let name = taxonomy.name();
+        out_paths.insert(
+            Utf8PathBuf::from(name).join("index.html"),
+            format!("the index of {}", name),
+        );
+        for (slug, count) in counts {
+            let group_path = Utf8PathBuf::from(taxonomy.term_url_path(&slug));
+            let description = format!("the {} listing of `{}`", name, slug);
+            for index in 0..count.div_ceil(page_size as usize) {
+                let out_path = group_path.join(format!("{}/index.html", index));
+                out_paths.insert(out_path, description.clone());
+            }
+            out_paths.insert(group_path.join("index.html"), description);
+        }
+    }
+    out_paths
+}
+
+/// Make sure no two pages get written to the same place, as is easy enough to do by accident once
+/// date prefixes get stripped from file names. Nor may a page take the place of a taxonomy listing.
+fn check_out_paths(
+    site_nodes: &[SiteNode],
+    taxonomy_rules: &HashMap<Taxonomy, Arc<RenderRules>>,
+) -> anyhow::Result<()> {
+    let mut sources_by_out_path = taxonomy_out_paths(site_nodes, taxonomy_rules);
+    for site_entry in site_nodes.iter().flat_map(|node| node.site_entries.iter()) {
+        let source = site_entry.file.rel_path.to_string();
+        if let Some(other) = sources_by_out_path.insert(site_entry.out_path.clone(), source.clone())
+        {
+            let mut sources = [other, source];
+            sources.sort();
+            return Err(anyhow!(
+                "{} and {} would both be written to {}",
+                sources[0],
+                sources[1],
+                site_entry.out_path
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Index every page of the site, for the sake of resolving wiki-style links.
+fn build_site_index(site_nodes: &[SiteNode]) -> SiteIndex {
+    let mut index = SiteIndex::default();
+    for site_entry in site_nodes.iter().flat_map(|node| node.site_entries.iter()) {
+        let title = site_entry
+            .valid_frontmatter()
+            .map(|frontmatter| frontmatter.title.as_str());
+        index.insert(&site_entry.file.rel_path, &site_entry.url_path, title);
+    }
+    index
+}
+
+fn parse_page_data(
+    config: &Config,
+    site_entry: &SiteEntry,
+    render_rules: &RenderRules,
+    renderer: &Renderer,
+    site_index: &SiteIndex,
+    file_content: FileContent,
+    frontmatter: Option<Result<(FrontMatter, usize), MarkdownError>>,
+) -> anyhow::Result<PageData> {
     match site_entry.get_page_type() {
         PageType::Markdown => {
-            let markdown = markdown::parse(&file_content)?;
-            Ok(PageData::Markdown(markdown))
+            let abs_path = &site_entry.file.abs_path;
+            let path = abs_path
+                .strip_prefix(&config.project_dir)
+                .unwrap_or(abs_path);
+            let (frontmatter, offset) = frontmatter
+                .expect("frontmatter is read as soon as the content is loaded")
+                .map_err(|e| e.with_path(path))?;
+            let body = &file_content[offset..];
+            let mut markdown = match render_rules.parser {
+                MarkdownBackend::Native => markdown::parse_body(frontmatter, body),
+                MarkdownBackend::CommonMark => {
+                    warn_native_only_syntax(path, &file_content, offset);
+                    commonmark::parse_body(frontmatter, body)
+                }
+            };
+            // A `summary` given in the frontmatter is parsed apart from the body, so gets checked
+            // on its own.
+            for blocks in [&markdown.blocks, &markdown.summary] {
+                markdown::check_shortcodes(&file_content, blocks, &|name| {
+                    renderer.has_partial(&shortcode_partial_name(name))
+                })
+                .map_err(|e| e.with_path(path))?;
+            }
+            let rel_dir = site_entry.file.rel_dir();
+            let mut links = vec![];
+            // The summary & footnotes are copies of (or separate from) the main blocks, so they
+            // need the same treatment.
+            for blocks in [
+                &mut markdown.blocks,
+                &mut markdown.summary,
+                &mut markdown.footnotes,
+            ] {
+                wikilinks::resolve_wikilinks(
+                    &file_content,
+                    blocks,
+                    site_index,
+                    &rel_dir,
+                    &mut links,
+                )
+                .map_err(|e| e.with_path(path))?;
+                math::render_math(&file_content, blocks).map_err(|e| e.with_path(path))?;
+                markdown::resolve_image_sources(blocks, &rel_dir);
+                if let Some(highlight_rules) = &render_rules.highlight {
+                    highlight::highlight_code(blocks, highlight_rules)?;
+                }
+                if render_rules.smart_typography {
+                    typography::smarten(blocks);
+                }
+            }
+            markdown.links = links;
+            Ok(PageData::Markdown(Box::new(markdown)))
         }
         PageType::Liquid => Ok(PageData::Liquid(file_content)),
         PageType::Html => Ok(PageData::Html(file_content)),
     }
 }
 
+/// Log a page that failed to generate, along with the offending source line if there is one.
+fn report_page_error(site_entry: &SiteEntry, error: &anyhow::Error) {
+    match error
+        .downcast_ref::<markdown::MarkdownError>()
+        .and_then(|e| e.location())
+    {
+        Some(location) => tracing::error!("{}\n    | {}", error, location.snippet),
+        None => tracing::error!("{}: {}", site_entry.file.rel_path, error),
+    }
+}
+
+/// Warn about any syntax in the body of a page, starting at `offset` into its `contents`, that
+/// only the native parser understands, since the CommonMark one would let it through as text.
+fn warn_native_only_syntax(path: &Utf8Path, contents: &str, offset: usize) {
+    for (found, syntax) in commonmark::native_only_syntax(&contents[offset..]) {
+        let mut location = SourceLocation::new(contents, offset + found);
+        location.path = path.to_owned();
+        tracing::warn!(
+            "{}: {} is only supported by the native markdown parser\n    | {}",
+            location,
+            syntax,
+            location.snippet
+        );
+    }
+}
+
 fn copy_previously_generated<C: Deref<Target = Config>, P: AsRef<Path>>(
     config: &C,
     site_entry: &SiteEntry,
@@ -111,6 +338,8 @@ type RenderListingChannelItem = (String, Arc<RenderRules>);
 struct Generator {
     config: Arc<Config>,
     staging_dir: Arc<TempDir>, // TODO do an AsRef<Path> on this?
+    // Count of pages that could not be generated.
+    failures: Arc<AtomicUsize>,
 }
 
 impl Generator {
@@ -120,6 +349,7 @@ impl Generator {
         Ok(Self {
             config,
             staging_dir,
+            failures: Arc::new(AtomicUsize::new(0)),
         })
     }
 
@@ -137,6 +367,20 @@ impl Generator {
             })
             .collect();
         join_all(load_content_futures).await;
+        for site_entry in site_nodes
+            .iter_mut()
+            .flat_map(|node| &mut node.site_entries)
+        {
+            site_entry.read_frontmatter();
+        }
+        let unpublished_dirs = if self.config.drafts {
+            HashSet::new()
+        } else {
+            drop_unpublished(&conn, &mut site_nodes).await?
+        };
+        infer_timestamps(&self.config.pages_dir(), &mut site_nodes)?;
+        let taxonomy_rules = collect_taxonomy_rules(&site_nodes);
+        check_out_paths(&site_nodes, &taxonomy_rules)?;
 
         let mut templates = collect_templates(&self.config).await;
         let load_content_futures = templates
@@ -145,11 +389,18 @@ impl Generator {
         join_all(load_content_futures).await;
 
         let (asset_map, assets_have_changed) = self.collect_assets(&site_nodes, &templates).await?;
+        diskio::copy_page_assets(
+            &self.config.pages_dir(),
+            self.staging_dir.path(),
+            &unpublished_dirs,
+        )
+        .await?;
+        let site_index = Arc::new(build_site_index(&site_nodes));
         let force_render = {
             let conn = cache::new_connection().await?;
-            self.config.no_cache
-                || assets_have_changed
-                || check_latest_modified_template(&conn, &templates).await
+            // Checked regardless, so that the latest modification time gets recorded either way.
+            let templates_have_changed = check_latest_modified_template(&conn, &templates).await;
+            self.config.no_cache || assets_have_changed || templates_have_changed
         };
         let renderer = Arc::new(Renderer::new(&self.config, asset_map, templates).await);
 
@@ -165,7 +416,7 @@ impl Generator {
             let post_render_tx = post_render_tx.clone();
             self.run_pre_render_pipeline(
                 site_nodes,
-                force_render,
+                (!force_render).then(|| site_index.clone()),
                 post_render_tx,
                 render_tx,
                 render_listing_tx,
@@ -173,18 +424,30 @@ impl Generator {
         };
 
         // Render pipeline
-        let render_handle = self.run_render_pipeline(renderer.clone(), render_rx, post_render_tx);
+        let render_handle =
+            self.run_render_pipeline(renderer.clone(), site_index, render_rx, post_render_tx);
 
         // Post-render pipeline
         let post_render_handle = self.run_post_render_pipeline(post_render_rx);
 
-        tokio::join!(pre_render_handle, render_handle, post_render_handle,);
+        let (_, _, aliases) = tokio::join!(pre_render_handle, render_handle, post_render_handle,);
 
         while let Some((dir, render_rules)) = render_listing_rx.recv().await {
             self.generate_listing(&renderer, &render_rules, dir)
                 .await
                 .unwrap();
         }
+        for (taxonomy, render_rules) in taxonomy_rules {
+            self.generate_taxonomy(&renderer, &render_rules, taxonomy)
+                .await?;
+        }
+        self.write_redirects(aliases)?;
+
+        // Leave the old output directory alone rather than publish a partial site.
+        let failures = self.failures.load(Ordering::Relaxed);
+        if failures > 0 {
+            return Err(anyhow!("failed to generate {} page(s)", failures));
+        }
 
         // Replace the old output directory with the new one.
         std::fs::remove_dir_all(self.config.out_dir()).unwrap();
@@ -233,13 +496,28 @@ impl Generator {
             )
         };
         asset_map.insert(tailwind_alias.clone(), tailwind_cache_busted.await.unwrap());
+
+        // Stylesheets for any themes used for class-based code highlighting.
+        let themes = site_nodes
+            .iter()
+            .filter_map(|node| node.render_rules.highlight.as_ref())
+            .filter(|rules| rules.style == HighlightStyle::Classes)
+            .map(|rules| rules.theme.as_str())
+            .collect::<HashSet<_>>();
+        for theme in themes {
+            let alias = highlight::stylesheet_alias(theme);
+            let css = highlight::theme_css(theme)?;
+            let cache_busted =
+                assets::write_generated_asset(&alias, css.as_bytes(), self.staging_dir.path());
+            asset_map.insert(alias, cache_busted);
+        }
         Ok((asset_map, assets_have_changed))
     }
 
     async fn run_pre_render_pipeline(
         &self,
         site_nodes: Vec<SiteNode>,
-        force_render: bool,
+        site_index: Option<Arc<SiteIndex>>,
         post_render_tx: tokio::sync::mpsc::Sender<PostRenderChannelItem>,
         render_tx: tokio::sync::mpsc::Sender<RenderChannelItem>,
         render_listing_tx: tokio::sync::mpsc::Sender<RenderListingChannelItem>,
@@ -249,7 +527,7 @@ impl Generator {
             self.route_node(
                 &conn,
                 site_node,
-                force_render,
+                site_index.as_deref(),
                 &post_render_tx,
                 &render_tx,
                 &render_listing_tx,
@@ -261,23 +539,26 @@ impl Generator {
 
     /// See what of the `node` can be restored from the cache.
     /// Copy what can, and send what cannot for further processing in the pipeline.
+    /// Without a `site_index` to check the links of cached pages against, everything gets
+    /// rendered afresh.
     async fn route_node(
         &self,
         conn: &Connection,
         node: SiteNode,
-        force_render: bool,
+        site_index: Option<&SiteIndex>,
         post_render_tx: &tokio::sync::mpsc::Sender<PostRenderChannelItem>,
         render_tx: &tokio::sync::mpsc::Sender<RenderChannelItem>,
         render_listing_tx: &tokio::sync::mpsc::Sender<RenderListingChannelItem>,
     ) -> anyhow::Result<()> {
         for site_entry in node.site_entries {
-            if !force_render && let Some(cached_page_data) =
-                // TODO this should be an async fn
-                self.try_restore_from_cache(conn, &site_entry).await?
+            if let Some(site_index) = site_index
+                && let Some(cached_page_data) =
+                    // TODO this should be an async fn
+                    self
+                        .try_restore_from_cache(conn, &site_entry, site_index)
+                        .await?
             {
-                post_render_tx
-                    .send((site_entry, cached_page_data))
-                    .await?;
+                post_render_tx.send((site_entry, cached_page_data)).await?;
             } else {
                 render_tx
                     .send((site_entry, node.render_rules.clone()))
@@ -301,8 +582,21 @@ impl Generator {
         &self,
         conn: &Connection,
         site_entry: &SiteEntry,
+        site_index: &SiteIndex,
     ) -> anyhow::Result<Option<CachedPageData>> {
         if let Some(cached_page_data) = cache::restore_cached(conn, site_entry).await? {
+            // Pages linked to may have moved or been retitled since.
+            if let CachedPageData::Markdown(_, markdown, _) = &cached_page_data {
+                let rel_dir = site_entry.file.rel_dir();
+                if !markdown
+                    .links
+                    .iter()
+                    .all(|link| site_index.resolves_same(link, &rel_dir))
+                {
+                    tracing::info!("links of {:?} have changed", site_entry.file.rel_path);
+                    return Ok(None);
+                }
+            }
             match copy_previously_generated(&self.config, site_entry, self.staging_dir.as_ref()) {
                 Ok(_) => {
                     tracing::info!(
@@ -323,6 +617,7 @@ impl Generator {
     async fn run_render_pipeline(
         &self,
         renderer: Arc<Renderer>,
+        site_index: Arc<SiteIndex>,
         mut render_rx: tokio::sync::mpsc::Receiver<RenderChannelItem>,
         post_render_tx: tokio::sync::mpsc::Sender<PostRenderChannelItem>,
     ) {
@@ -342,20 +637,49 @@ impl Generator {
         while let Some((mut site_entry, render_rules)) = render_rx.recv().await {
             let rayon_tx = rayon_tx.clone();
             let renderer = renderer.clone();
+            let site_index = site_index.clone();
+            let config = self.config.clone();
+            let failures = self.failures.clone();
             // Shouldn't actually be awaiting this for long, since we've likely had it loaded.
             let file_content = site_entry.file.get_content().await.unwrap();
+            let frontmatter = site_entry.frontmatter.take();
             rayon::spawn(move || {
                 tracing::debug!("rendering page: {:?}", site_entry.file.rel_path);
                 let hash = file_content.hash;
-                let page_data = parse_page_data(&site_entry, file_content).unwrap();
-                let rendered = renderer
-                    .render(&page_data, &render_rules, &render_rules.layouts)
-                    .unwrap();
+                // A broken page shouldn't hold up the rest of the site, so just report & skip it.
+                let rendered = parse_page_data(
+                    &config,
+                    &site_entry,
+                    &render_rules,
+                    &renderer,
+                    &site_index,
+                    file_content,
+                    frontmatter,
+                )
+                .and_then(|page_data| {
+                    let render_rules = match &page_data {
+                        PageData::Markdown(md) => render_rules.for_page(&md.frontmatter),
+                        _ => render_rules,
+                    };
+                    let rendered =
+                        renderer.render(&page_data, &render_rules, &render_rules.layouts)?;
+                    Ok((page_data, rendered))
+                });
+                let (page_data, rendered) = match rendered {
+                    Ok(rendered) => rendered,
+                    Err(e) => {
+                        report_page_error(&site_entry, &e);
+                        failures.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                };
                 let cached_page_data = match page_data {
                     PageData::Markdown(md) => CachedPageData::Markdown(hash, md, rendered),
                     PageData::Liquid(_) => CachedPageData::Liquid(hash, rendered),
                     PageData::Html(_) => CachedPageData::Html(hash, rendered),
-                    PageData::Listing(..) => unimplemented!(),
+                    PageData::Listing(..) | PageData::TermListing(..) | PageData::Terms(..) => {
+                        unimplemented!()
+                    }
                 };
                 rayon_tx
                     .send((site_entry, cached_page_data))
@@ -364,11 +688,18 @@ impl Generator {
         }
     }
 
+    /// Write out the rendered pages & cache them, returning any aliases they declare.
     async fn run_post_render_pipeline(
         &self,
         mut post_render_rx: tokio::sync::mpsc::Receiver<PostRenderChannelItem>,
-    ) {
+    ) -> Vec<Alias> {
+        let mut aliases = vec![];
         while let Some((site_entry, cached_page_data)) = post_render_rx.recv().await {
+            if let CachedPageData::Markdown(_, md, _) = &cached_page_data {
+                aliases.extend(md.frontmatter.aliases.iter().map(|alias| {
+                    Alias::new(&site_entry.file.rel_path, alias, &site_entry.url_path)
+                }));
+            }
             let staging_path = self.staging_dir.path().to_path_buf();
             tracing::debug!("writing rendered page to disk");
             // TODO really should use async rusqlite for this...
@@ -383,6 +714,42 @@ impl Generator {
                 .await
                 .unwrap();
         }
+        aliases
+    }
+
+    /// Write a redirect page for each of the `aliases`, plus a manifest of all of them.
+    /// Must come last, so that any alias at the path of something else can be caught.
+    fn write_redirects(&self, mut aliases: Vec<Alias>) -> anyhow::Result<()> {
+        aliases.sort_by(|a, b| a.from.cmp(&b.from));
+        let mut written = vec![];
+        for alias in aliases {
+            let Some(out_path) = alias.out_path() else {
+                tracing::error!(
+                    "{}: alias {} leads outside of the output directory",
+                    alias.page,
+                    alias.from
+                );
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            let out_path = self.staging_dir.path().join(out_path);
+            if out_path.exists() {
+                tracing::error!(
+                    "{}: alias {} collides with an existing page",
+                    alias.page,
+                    alias.from
+                );
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            diskio::write_html_sync(out_path, &alias.redirect_page())?;
+            written.push(alias);
+        }
+        if !written.is_empty() {
+            let manifest_path = self.staging_dir.path().join(redirects::MANIFEST_FILENAME);
+            fs::write(manifest_path, redirects::manifest(&written))?;
+        }
+        Ok(())
     }
 
     async fn generate_listing<R: Deref<Target = RenderRules>>(
@@ -391,41 +758,102 @@ impl Generator {
         render_rules: &R,
         group_path: String,
     ) -> anyhow::Result<()> {
-        let conn = cache::new_connection().await?;
         // Should be OK to unwrap here.
-        let page_size = render_rules
-            .listing
-            .as_ref()
-            .unwrap()
-            .page_size
-            .unwrap_or(DEFAULT_LISTING_PAGE_SIZE);
-        // TODO need to get the right pagination count.
-        // TODO also should be able to restore cached renders from the db!
-        let page_count = divide_round_up(
-            cache::get_page_group_count(&conn, &group_path).await?,
+        let listing_rules = render_rules.listing.as_ref().unwrap();
+        let page_size = listing_rules.page_size.unwrap_or(DEFAULT_LISTING_PAGE_SIZE);
+        self.write_listing_pages(
+            cache::ListingGroup::Children(&group_path),
             page_size,
-        );
-        let stream = cache::markdown_stream(conn, &group_path, page_size);
+            &group_path,
+            |group, page_index| {
+                let page_data = PageData::Listing(group_path.clone(), group, page_index);
+                renderer.render(&page_data, render_rules, &listing_rules.layouts)
+            },
+        )
+        .await
+    }
+
+    /// Render the pages of the `group`, `page_size` at a time, to `<group_path>/<index>/`.
+    async fn write_listing_pages(
+        &self,
+        group: cache::ListingGroup<'_>,
+        page_size: u8,
+        group_path: &str,
+        render: impl Fn(Vec<(Markdown, String)>, PageIndex) -> RenderResult<String>,
+    ) -> anyhow::Result<()> {
+        let conn = cache::new_connection().await?;
+        // TODO also should be able to restore cached renders from the db!
+        let page_count = divide_round_up(cache::get_listing_count(&conn, group).await?, page_size);
+        let stream = cache::markdown_stream(conn, group, page_size).enumerate();
         futures::pin_mut!(stream);
-        let index = 0; // TODO enumerate
-        while let Some(group) = stream.next().await {
-            // TODO need to get the page count (sqlite also).
-            let page_data = PageData::Listing(group_path.clone(), group, (index, page_count));
-            let rendered = renderer.render(
-                &page_data,
-                render_rules,
-                &render_rules.listing.as_ref().unwrap().layouts,
-            )?;
+        while let Some((index, entries)) = stream.next().await {
+            let rendered = render(entries, (index as u8, page_count))?;
             let out_path = self
                 .staging_dir
                 .path()
-                .join(&group_path)
+                .join(group_path)
                 .join(format!("{}/index.html", index));
             fs::create_dir_all(out_path.parent().unwrap())?;
             diskio::write_html_sync(out_path, &rendered)?;
         }
         Ok(())
     }
+
+    /// Generate the index of every term of the `taxonomy`, plus a listing of the pages filed
+    /// under each of them.
+    async fn generate_taxonomy(
+        &self,
+        renderer: &Renderer,
+        render_rules: &RenderRules,
+        taxonomy: Taxonomy,
+    ) -> anyhow::Result<()> {
+        // Only called for taxonomies found in the rules, so indexing is OK.
+        let taxonomy_rules = &render_rules.taxonomies[&taxonomy];
+        let page_size = taxonomy_rules
+            .page_size
+            .unwrap_or(DEFAULT_LISTING_PAGE_SIZE);
+        let conn = cache::new_connection().await?;
+        let terms = cache::get_terms(&conn, taxonomy).await?;
+        for term in terms.iter() {
+            let group_path = taxonomy.term_url_path(&term.slug);
+            self.write_listing_pages(
+                cache::ListingGroup::Term(taxonomy, &term.slug),
+                page_size,
+                &group_path,
+                |group, page_index| {
+                    let page_data = PageData::TermListing(term.clone(), group, page_index);
+                    renderer.render(&page_data, &render_rules, &taxonomy_rules.layouts)
+                },
+            )
+            .await?;
+            // The first page also goes at the url of the term itself.
+            let term_dir = self.staging_dir.path().join(&group_path);
+            fs::copy(term_dir.join("0/index.html"), term_dir.join("index.html"))?;
+        }
+        let page_data = PageData::Terms(taxonomy, terms);
+        let rendered = renderer.render(&page_data, &render_rules, &taxonomy_rules.index_layouts)?;
+        let out_path = self
+            .staging_dir
+            .path()
+            .join(taxonomy.name())
+            .join("index.html");
+        fs::create_dir_all(out_path.parent().unwrap())?;
+        diskio::write_html_sync(out_path, &rendered)?;
+        Ok(())
+    }
+}
+
+/// The rules configuring each taxonomy, as found in the rules of any directory.
+fn collect_taxonomy_rules(site_nodes: &[SiteNode]) -> HashMap<Taxonomy, Arc<RenderRules>> {
+    let mut taxonomy_rules = HashMap::new();
+    for node in site_nodes {
+        for taxonomy in node.render_rules.taxonomies.keys() {
+            taxonomy_rules
+                .entry(*taxonomy)
+                .or_insert_with(|| node.render_rules.clone());
+        }
+    }
+    taxonomy_rules
 }
 
 /// Generate the site.
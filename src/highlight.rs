@@ -0,0 +1,147 @@
+use syntect::highlighting::ThemeSet;
+use syntect::html::{
+    css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle, ClassedHTMLGenerator,
+};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use thiserror::Error;
+
+use crate::common::{Block, HighlightRules, HighlightStyle, Token};
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+// Keeps the highlighting classes from clashing with any of the site's own.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+#[derive(Error, Debug)]
+pub enum HighlightError {
+    #[error("unknown highlighting theme `{0}`")]
+    UnknownTheme(String),
+    #[error("failed to highlight code")]
+    SyntectError(#[from] syntect::Error),
+}
+
+type HighlightResult<T> = Result<T, HighlightError>;
+
+/// The static asset alias for the stylesheet of the `theme`, for class-based highlighting.
+pub fn stylesheet_alias(theme: &str) -> String {
+    format!("highlight-{}.css", theme)
+}
+
+/// Generate the stylesheet of the `theme`, for class-based highlighting.
+pub fn theme_css(theme: &str) -> HighlightResult<String> {
+    let theme = THEME_SET
+        .themes
+        .get(theme)
+        .ok_or_else(|| HighlightError::UnknownTheme(theme.to_string()))?;
+    Ok(css_for_theme_with_class_style(theme, CLASS_STYLE)?)
+}
+
+/// Highlight every `pre` block with a `language` that we know of, storing the resulting HTML in
+/// its `meta` as `highlighted`.
+pub fn highlight_code(blocks: &mut [Block], rules: &HighlightRules) -> HighlightResult<()> {
+    for block in blocks {
+        if block.kind == "pre" {
+            let Some(meta) = block.meta.as_mut() else {
+                continue;
+            };
+            let (Some(language), Some(Token::Literal(code))) =
+                (meta.get("language"), block.tokens.first())
+            else {
+                continue;
+            };
+            if let Some(highlighted) = highlight(code, language, rules)? {
+                meta.insert("highlighted".to_string(), highlighted);
+            }
+            continue;
+        }
+        for token in block.tokens.iter_mut() {
+            if let Token::Block(nested) = token {
+                highlight_code(std::slice::from_mut(nested), rules)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render the `code` as highlighted HTML, or `None` if the `language` is unknown.
+fn highlight(
+    code: &str,
+    language: &str,
+    rules: &HighlightRules,
+) -> HighlightResult<Option<String>> {
+    let Some(syntax) = SYNTAX_SET.find_syntax_by_token(language) else {
+        return Ok(None);
+    };
+    // The trailing newline was stripped in parsing, but the syntax definitions expect it.
+    let code = format!("{}\n", code);
+    let html = match rules.style {
+        HighlightStyle::Classes => {
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+            for line in LinesWithEndings::from(&code) {
+                generator.parse_html_for_line_which_includes_newline(line)?;
+            }
+            format!(
+                "<pre class=\"hl-code\"><code class=\"language-{}\">{}</code></pre>",
+                language,
+                generator.finalize()
+            )
+        }
+        HighlightStyle::Inline => {
+            let theme = THEME_SET
+                .themes
+                .get(&rules.theme)
+                .ok_or_else(|| HighlightError::UnknownTheme(rules.theme.clone()))?;
+            highlighted_html_for_string(&code, &SYNTAX_SET, syntax, theme)?
+        }
+    };
+    Ok(Some(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::markdown::parse_blocks;
+
+    #[test]
+    fn test_highlight_code() {
+        let input = "```rust\nlet x = \"<y>\";\n```\n\n> ```nonexistent\n> code\n> ```";
+        let classes = HighlightRules {
+            theme: "InspiredGitHub".to_string(),
+            style: HighlightStyle::Classes,
+        };
+        let mut blocks = parse_blocks(input);
+        highlight_code(&mut blocks, &classes).unwrap();
+        let highlighted = &blocks[0].meta.as_ref().unwrap()["highlighted"];
+        assert!(highlighted.starts_with("<pre class=\"hl-code\"><code class=\"language-rust\">"));
+        assert!(highlighted.contains("<span class=\"hl-storage hl-type hl-rust\">let</span>"));
+        assert!(highlighted.contains("&lt;y&gt;"));
+        // Unknown languages are left as is.
+        let Token::Block(quoted) = &blocks[1].tokens[0] else {
+            panic!("expected a code block");
+        };
+        assert!(!quoted.meta.as_ref().unwrap().contains_key("highlighted"));
+
+        let inline = HighlightRules {
+            style: HighlightStyle::Inline,
+            ..classes
+        };
+        let mut blocks = parse_blocks(input);
+        highlight_code(&mut blocks, &inline).unwrap();
+        assert!(blocks[0].meta.as_ref().unwrap()["highlighted"].starts_with("<pre style="));
+
+        let unknown = HighlightRules {
+            theme: "nope".to_string(),
+            ..inline
+        };
+        assert!(matches!(
+            highlight_code(&mut blocks, &unknown),
+            Err(HighlightError::UnknownTheme(_))
+        ));
+        assert!(theme_css("InspiredGitHub").unwrap().contains(".hl-code"));
+    }
+}
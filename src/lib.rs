@@ -7,17 +7,20 @@ extern crate lazy_static;
 mod config;
 pub mod diskio;
 mod generator;
+mod highlight;
 mod server;
 pub(crate) mod liquid {
     pub(crate) mod filters {
         mod block;
+        mod date;
         pub use block::FirstBlockOfKind;
+        pub use date::Date;
     }
     pub(crate) mod tags {
         mod render_block;
         mod static_asset;
         mod tailwind;
-        pub use render_block::RenderBlockTag;
+        pub use render_block::{shortcode_partial_name, RenderBlockTag};
         pub use static_asset::StaticAssetTag;
         pub use tailwind::TailwindTag;
     }
@@ -27,6 +30,7 @@ pub(crate) mod cache;
 pub(crate) mod common;
 pub(crate) mod parsers;
 pub mod project;
+mod redirects;
 mod renderer;
 mod utils;
 
@@ -0,0 +1,125 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset};
+use liquid_core::runtime::Variable;
+use liquid_core::{
+    Display_filter, Expression, Filter, FilterParameters, FilterReflection, FromFilterParameters,
+    ParseFilter, Result, Runtime, Value, ValueView,
+};
+
+use crate::common::Timezone;
+use crate::renderer::TIMEZONE_TEMPLATE_VAR;
+
+// Shows a time as it was written, even if the rules configure a timezone.
+const ORIGINAL_TIMEZONE: &str = "original";
+
+#[derive(Debug, FilterParameters)]
+struct DateArgs {
+    #[parameter(description = "The format to return the date in.", arg_type = "str")]
+    format: Expression,
+    #[parameter(
+        description = "The timezone to show the date in, or `original` for the offset it was \
+                       written with. Defaults to the timezone of the rules, if any.",
+        arg_type = "str"
+    )]
+    timezone: Option<Expression>,
+}
+
+// Takes the place of the `date` filter of the standard library, which can't read RFC 3339.
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "date",
+    description = "Formats a timestamp, in the timezone of the rules or the one it was written in.",
+    parameters(DateArgs),
+    parsed(DateFilter)
+)]
+pub struct Date;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "date"]
+struct DateFilter {
+    #[parameters]
+    args: DateArgs,
+}
+
+/// Read a time given as RFC 3339, as in the frontmatter, or in any form liquid itself knows.
+fn parse_time(input: &dyn ValueView) -> Option<DateTime<FixedOffset>> {
+    let input = input.as_scalar()?;
+    DateTime::parse_from_rfc3339(input.to_kstr().as_str())
+        .ok()
+        .or_else(|| {
+            let time = input.to_date_time()?.to_string();
+            DateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S%.f %z").ok()
+        })
+}
+
+/// The timezone configured by the rules of the page being rendered.
+fn configured_timezone(runtime: &dyn Runtime) -> Result<Option<Timezone>> {
+    let path = Variable::with_literal(TIMEZONE_TEMPLATE_VAR);
+    let path = path.evaluate(runtime)?;
+    Ok(runtime
+        .try_get(&path)
+        .and_then(|timezone| timezone.as_scalar()?.to_kstr().parse().ok()))
+}
+
+impl Filter for DateFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+
+        // Like the standard library, leave alone whatever isn't a time, e.g. a missing `updated`.
+        let Some(time) = parse_time(input) else {
+            return Ok(input.to_value());
+        };
+        if args.format.is_empty() {
+            return Ok(input.to_value());
+        }
+        let timezone = match args.timezone.as_deref() {
+            Some(ORIGINAL_TIMEZONE) => None,
+            Some(timezone) => Some(timezone.parse().map_err(liquid_core::Error::with_msg)?),
+            None => configured_timezone(runtime)?,
+        };
+        let time = timezone.map_or(time, |timezone| timezone.convert(&time));
+
+        let mut formatted = String::new();
+        write!(formatted, "{}", time.format(&args.format)).map_err(|_| {
+            liquid_core::Error::with_msg(format!("Invalid date-format string: {}", args.format))
+        })?;
+        Ok(Value::scalar(formatted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        let timestamp = "2023-10-21T22:30:00-05:00";
+        assert_eq!(
+            liquid_core::call_filter!(Date, timestamp, "%Y-%m-%d %H:%M %:z").unwrap(),
+            liquid_core::value!("2023-10-21 22:30 -05:00"),
+        );
+        assert_eq!(
+            liquid_core::call_filter!(Date, timestamp, "%Y-%m-%d %H:%M", "+02:00").unwrap(),
+            liquid_core::value!("2023-10-22 05:30"),
+        );
+        assert_eq!(
+            liquid_core::call_filter!(Date, timestamp, "%H:%M %:z", "Europe/Berlin").unwrap(),
+            liquid_core::value!("05:30 +02:00"),
+        );
+        assert_eq!(
+            liquid_core::call_filter!(Date, timestamp, "%H:%M", "original").unwrap(),
+            liquid_core::value!("22:30"),
+        );
+        assert_eq!(
+            liquid_core::call_filter!(Date, "13 Jun 2016 02:30:00 +0300", "%Y-%m-%d").unwrap(),
+            liquid_core::value!("2016-06-13"),
+        );
+        assert_eq!(
+            liquid_core::call_filter!(Date, "", "%Y-%m-%d").unwrap(),
+            liquid_core::value!(""),
+        );
+        liquid_core::call_filter!(Date, timestamp, "%Y", "Mars/Olympus_Mons").unwrap_err();
+        liquid_core::call_filter!(Date, timestamp, "%Y %8").unwrap_err();
+    }
+}
@@ -58,6 +58,11 @@ fn find_partial_name(block_rules: Option<&dyn ObjectView>, kind: &str) -> Result
     Ok(format!("blocks/{}.liquid", partial_name))
 }
 
+/// The partial that renders the shortcode called `name`.
+pub fn shortcode_partial_name(name: &str) -> String {
+    format!("blocks/shortcodes/{}.liquid", name)
+}
+
 fn render_block(
     runtime: &dyn Runtime,
     block: &dyn ObjectView,
@@ -74,6 +79,9 @@ fn render_block(
         .as_array()
         .ok_or(Error::with_msg("Malformed block - `tokens` not an array"))?;
 
+    // Child blocks are also handed to the template individually, so that it can lay them out
+    // itself, e.g. as tabs.
+    let mut children = vec![];
     let content = tokens
         .values()
         .filter_map(|token| {
@@ -82,7 +90,13 @@ fn render_block(
                 Some(literal.to_kstr().as_str().into())
             } else if let Some(nested_block) = token.get("Block") {
                 let nested_block = nested_block.as_object().unwrap();
-                render_block(runtime, nested_block, block_rules).ok()
+                let rendered = render_block(runtime, nested_block, block_rules).ok()?;
+                children.push(liquid::object!({
+                    "kind": nested_block.get("kind").map(|v| v.to_value()),
+                    "meta": nested_block.get("meta").map(|v| v.to_value()),
+                    "content": rendered.clone(),
+                }));
+                Some(rendered)
             } else {
                 // TODO or error out here, because this would imply something is malformed...
                 None
@@ -93,12 +107,28 @@ fn render_block(
 
     let meta = block.get("meta").map(|v| v.to_value());
 
-    let partial_name = find_partial_name(block_rules, &kind)?;
-    let partial = runtime.partials().get(&partial_name)?;
-    let pass_through = liquid::object!({
+    let mut pass_through = liquid::object!({
         "content": content,
         "meta": meta,
+        "children": children,
     });
+    let partial_name = if kind == "shortcode" {
+        let meta = block
+            .get("meta")
+            .and_then(|meta| meta.as_object())
+            .ok_or(Error::with_msg("Malformed shortcode - missing `meta`"))?;
+        let name = meta
+            .get("shortcode")
+            .ok_or(Error::with_msg("Malformed shortcode - missing name"))?;
+        // Shortcode arguments are handed over as plain variables.
+        for (key, value) in meta.iter() {
+            pass_through.insert(key.into(), value.to_value());
+        }
+        shortcode_partial_name(name.to_kstr().as_str())
+    } else {
+        find_partial_name(block_rules, &kind)?
+    };
+    let partial = runtime.partials().get(&partial_name)?;
     let scope = StackFrame::new(runtime, &pass_through);
     partial.render(&scope)
 }
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::common::{Block, FrontMatter, Token};
+use crate::parsers::markdown;
+use crate::utils::escape_html;
+use crate::Markdown;
+
+/// Parse a `Markdown` struct from the `body` of a .md file, using a fully CommonMark (plus GFM
+/// tables, footnotes, strikethrough & task lists) compliant parser.
+/// Produces the same `Block` kinds as the native parser wherever the two overlap.
+pub fn parse_body(frontmatter: FrontMatter, body: &str) -> Markdown {
+    markdown::assemble(frontmatter, parse_blocks(body))
+}
+
+fn make_block(kind: &str, meta: Option<HashMap<String, String>>) -> Block {
+    Block {
+        kind: kind.to_string(),
+        tokens: vec![],
+        meta,
+    }
+}
+
+fn make_meta<const N: usize>(kvs: [(&str, String); N]) -> Option<HashMap<String, String>> {
+    Some(kvs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn alignment_meta(alignment: Option<&Alignment>) -> Option<HashMap<String, String>> {
+    let align = match alignment? {
+        Alignment::None => return None,
+        Alignment::Left => "left",
+        Alignment::Center => "center",
+        Alignment::Right => "right",
+    };
+    make_meta([("align", align.to_string())])
+}
+
+/// Flatten the literal text of `tokens`, e.g. for the alt text of an image.
+fn collect_text(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(literal) => literal.clone(),
+            Token::Block(block) => collect_text(&block.tokens),
+        })
+        .collect()
+}
+
+/// Keeps track of the `Block`s under construction as the events stream in.
+struct BlockBuilder {
+    // Innermost block last; the bottom of the stack collects the top-level blocks.
+    stack: Vec<Block>,
+    alignments: Vec<Alignment>,
+    in_table_head: bool,
+    cell_index: usize,
+}
+
+impl BlockBuilder {
+    fn new() -> Self {
+        Self {
+            stack: vec![make_block("", None)],
+            alignments: vec![],
+            in_table_head: false,
+            cell_index: 0,
+        }
+    }
+
+    fn push_token(&mut self, token: Token) {
+        // Bottom of the stack is always present, so `unwrap` is OK.
+        let tokens = &mut self.stack.last_mut().unwrap().tokens;
+        if let (Token::Literal(literal), Some(Token::Literal(last))) = (&token, tokens.last_mut()) {
+            last.push_str(literal);
+        } else {
+            tokens.push(token);
+        }
+    }
+
+    fn open(&mut self, block: Block) {
+        self.stack.push(block);
+    }
+
+    fn close(&mut self) {
+        if self.stack.len() > 1 {
+            let block = self.stack.pop().unwrap();
+            self.push_token(Token::Block(block));
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        let block = match tag {
+            Tag::Paragraph => make_block("p", None),
+            Tag::Heading { level, .. } => make_block(&level.to_string(), None),
+            Tag::BlockQuote => make_block("blockquote", None),
+            Tag::CodeBlock(kind) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(String::from),
+                    CodeBlockKind::Indented => None,
+                };
+                make_block("pre", language.and_then(|l| make_meta([("language", l)])))
+            }
+            Tag::HtmlBlock => make_block("html", None),
+            Tag::List(None) => make_block("ul", None),
+            Tag::List(Some(1)) => make_block("ol", None),
+            Tag::List(Some(start)) => make_block("ol", make_meta([("start", start.to_string())])),
+            Tag::Item => make_block("li", None),
+            Tag::FootnoteDefinition(label) => {
+                make_block("footnote", make_meta([("label", label.to_string())]))
+            }
+            Tag::Table(alignments) => {
+                self.alignments = alignments;
+                make_block("table", None)
+            }
+            Tag::TableHead => {
+                // Head cells come without a surrounding row, unlike in the native parser.
+                self.in_table_head = true;
+                self.cell_index = 0;
+                self.open(make_block("thead", None));
+                make_block("tr", None)
+            }
+            Tag::TableRow => {
+                self.cell_index = 0;
+                make_block("tr", None)
+            }
+            Tag::TableCell => {
+                let kind = if self.in_table_head { "th" } else { "td" };
+                let meta = alignment_meta(self.alignments.get(self.cell_index));
+                self.cell_index += 1;
+                make_block(kind, meta)
+            }
+            Tag::Emphasis => make_block("i", None),
+            Tag::Strong => make_block("b", None),
+            Tag::Strikethrough => make_block("s", None),
+            Tag::Link {
+                dest_url, title, ..
+            } => {
+                let mut meta = HashMap::from([("href".to_string(), dest_url.to_string())]);
+                if !title.is_empty() {
+                    meta.insert("title".to_string(), title.to_string());
+                }
+                make_block("a", Some(meta))
+            }
+            Tag::Image {
+                dest_url, title, ..
+            } => {
+                let mut meta = HashMap::from([("src".to_string(), dest_url.to_string())]);
+                if !title.is_empty() {
+                    meta.insert("title".to_string(), title.to_string());
+                }
+                make_block("img", Some(meta))
+            }
+            // Not enabled, since we handle frontmatter ourselves.
+            Tag::MetadataBlock(_) => make_block("", None),
+        };
+        self.open(block);
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::TableHead => {
+                self.in_table_head = false;
+                // Close the implicit row, then the head itself, then open up the body.
+                self.close();
+                self.close();
+                self.open(make_block("tbody", None));
+            }
+            TagEnd::Table => {
+                // Drop the body again if the table turned out to be all head.
+                if self.stack.last().is_some_and(|block| block.kind == "tbody") {
+                    let tbody = self.stack.pop().unwrap();
+                    if !tbody.tokens.is_empty() {
+                        self.push_token(Token::Block(tbody));
+                    }
+                }
+                self.close();
+            }
+            TagEnd::CodeBlock => {
+                // The native parser leaves off the newline before the closing fence.
+                if let Some(Token::Literal(code)) = self.stack.last_mut().unwrap().tokens.last_mut()
+                    && code.ends_with('\n')
+                {
+                    code.pop();
+                }
+                self.close();
+            }
+            TagEnd::HtmlBlock => {
+                let html = self.stack.last_mut().unwrap();
+                if let [Token::Literal(literal)] = html.tokens.as_slice()
+                    && markdown::is_summary_marker(literal)
+                {
+                    html.kind = markdown::SUMMARY_MARKER_KIND.to_string();
+                    html.tokens.clear();
+                }
+                self.close();
+            }
+            TagEnd::Image => {
+                // The alt text comes through as regular events, but belongs in the `meta`.
+                let image = self.stack.last_mut().unwrap();
+                let alt = collect_text(&image.tokens);
+                image.tokens.clear();
+                if let Some(meta) = image.meta.as_mut() {
+                    meta.insert("alt".to_string(), alt);
+                }
+                self.close();
+            }
+            _ => self.close(),
+        }
+    }
+
+    /// Whether text is to be kept raw rather than escaped as HTML: that of code blocks is escaped
+    /// by their template, or by the highlighter, and the alt text of images ends up in the `meta`.
+    fn keeps_raw_text(&self) -> bool {
+        self.stack.last().is_some_and(|block| block.kind == "pre")
+            || self.stack.iter().any(|block| block.kind == "img")
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.keeps_raw_text() => {
+                self.push_token(Token::Literal(text.to_string()))
+            }
+            // Entities & backslash escapes come decoded, so the text has to be escaped all over
+            // again.
+            Event::Text(text) => self.push_token(Token::Literal(escape_html(&text))),
+            Event::Html(html) => self.push_token(Token::Literal(html.to_string())),
+            // Inline HTML gets a block of its own, so that it's passed through as is.
+            Event::InlineHtml(html) => self.push_token(Token::Block(Block {
+                kind: "html".to_string(),
+                tokens: vec![Token::Literal(html.to_string())],
+                meta: None,
+            })),
+            Event::Code(code) if self.keeps_raw_text() => {
+                self.push_token(Token::Literal(code.to_string()))
+            }
+            Event::Code(code) => self.push_token(Token::Block(Block {
+                kind: "code".to_string(),
+                tokens: vec![Token::Literal(escape_html(&code))],
+                meta: None,
+            })),
+            Event::FootnoteReference(label) => self.push_token(Token::Block(make_block(
+                "footnote_ref",
+                make_meta([("label", label.to_string())]),
+            ))),
+            Event::SoftBreak => self.push_token(Token::Literal("\n".to_string())),
+            Event::HardBreak => self.push_token(Token::Block(make_block("br", None))),
+            Event::Rule => self.push_token(Token::Block(make_block("hr", None))),
+            Event::TaskListMarker(checked) => self.push_token(Token::Block(make_block(
+                "task",
+                make_meta([("checked", checked.to_string())]),
+            ))),
+        }
+    }
+
+    /// Finish up, returning the top-level blocks.
+    fn finish(mut self) -> Vec<Block> {
+        while self.stack.len() > 1 {
+            self.close();
+        }
+        self.stack
+            .pop()
+            .unwrap()
+            .tokens
+            .into_iter()
+            .map(|token| match token {
+                Token::Block(block) => block,
+                // Shouldn't really happen, but better to keep any stray text than drop it.
+                Token::Literal(literal) => Block {
+                    kind: "p".to_string(),
+                    tokens: vec![Token::Literal(literal)],
+                    meta: None,
+                },
+            })
+            .collect()
+    }
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+}
+
+/// Parse out the `body` of the post from the CommonMark event stream.
+pub fn parse_blocks(contents: &str) -> Vec<Block> {
+    let mut builder = BlockBuilder::new();
+    for event in Parser::new_ext(contents, options()) {
+        builder.event(event);
+    }
+    builder.finish()
+}
+
+/// Find the syntax in `contents` that only the native parser understands, which this one just
+/// leaves as text: math, wiki-style links, shortcodes, `:::` containers & `~:` block kinds.
+/// Returns the byte offset of each, along with a description of it.
+pub fn native_only_syntax(contents: &str) -> Vec<(usize, &'static str)> {
+    // The stretches of source that came through as text, outside of any code.
+    let mut spans: Vec<Range<usize>> = vec![];
+    let mut in_code_block = false;
+    for (event, range) in Parser::new_ext(contents, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(_) | Event::SoftBreak if !in_code_block => {
+                // Brackets & such may come as text of their own, so join up the pieces.
+                match spans.last_mut() {
+                    Some(span) if span.end == range.start => span.end = range.end,
+                    _ => spans.push(range),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut found = vec![];
+    for span in spans {
+        let text = &contents[span.clone()];
+        for (pattern, syntax) in [("[[", "a wiki-style link"), ("{{<", "a shortcode")] {
+            found.extend(
+                text.match_indices(pattern)
+                    .map(|(i, _)| (span.start + i, syntax)),
+            );
+        }
+        for (pattern, syntax) in [(":::", "a `:::` container"), ("~:", "a `~:` block kind")] {
+            found.extend(
+                text.match_indices(pattern)
+                    .map(|(i, _)| span.start + i)
+                    .filter(|&offset| {
+                        // Only at the start of a line, give or take blockquote markers.
+                        let line_start = contents[..offset].rfind('\n').map_or(0, |i| i + 1);
+                        contents[line_start..offset]
+                            .trim_start_matches(|c: char| c.is_whitespace() || c == '>')
+                            .is_empty()
+                    })
+                    .map(|offset| (offset, syntax)),
+            );
+        }
+        let mut rest = text;
+        while let Some(i) = rest.find('$') {
+            let (next, tokens) = markdown::parse_math_span(&rest[i..]);
+            if let [Token::Block(_)] = tokens.as_slice() {
+                found.push((span.end - rest.len() + i, "math"));
+            }
+            rest = next;
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::markdown::MarkdownError;
+
+    fn parse(contents: &str) -> Result<Markdown, MarkdownError> {
+        let (frontmatter, offset) = markdown::parse_frontmatter(contents)?;
+        Ok(parse_body(frontmatter, &contents[offset..]))
+    }
+
+    #[test]
+    fn test_matches_native_blocks() {
+        let input = "# Title\n\nSome *emphasis*, **strong** and `code` with a [link](/somewhere).\n\n> Quoted.\n\n- one\n- two\n\n3. three\n4. four\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|:--|--:|\n| 1 | 2 |\n\n![A cat](cat.png \"Cat\")\n";
+        assert_eq!(parse_blocks(input), markdown::parse_blocks(input));
+
+        // Special characters come out escaped either way, leaving entities & inline HTML alone,
+        // while the alt text & title of images are raw.
+        let input = "a < b && c > d, `<tag>` & `&amp;`\n\n5 &lt; 6 \\<x> <b>bold</b> <!-- note -->\n\n# [A & B](/ab) *<i>*\n\n![a < b](x.png \"t & u\")\n";
+        assert_eq!(parse_blocks(input), markdown::parse_blocks(input));
+    }
+
+    #[test]
+    fn test_parse_extensions() {
+        let input = "Hard  \nbreak ~~struck~~ <span>html</span>\n\n---\n\n- [x] done\n- [ ] todo\n";
+        let blocks = parse_blocks(input);
+        let kinds = blocks.iter().map(|b| b.kind.as_str()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["p", "hr", "ul"]);
+        assert_eq!(
+            blocks[0].tokens,
+            vec![
+                Token::Literal("Hard".to_string()),
+                Token::Block(make_block("br", None)),
+                Token::Literal("break ".to_string()),
+                Token::Block(Block {
+                    kind: "s".to_string(),
+                    tokens: vec![Token::Literal("struck".to_string())],
+                    meta: None,
+                }),
+                Token::Literal(" ".to_string()),
+                Token::Block(Block {
+                    kind: "html".to_string(),
+                    tokens: vec![Token::Literal("<span>".to_string())],
+                    meta: None,
+                }),
+                Token::Literal("html".to_string()),
+                Token::Block(Block {
+                    kind: "html".to_string(),
+                    tokens: vec![Token::Literal("</span>".to_string())],
+                    meta: None,
+                }),
+            ],
+        );
+        let Token::Block(item) = &blocks[2].tokens[0] else {
+            panic!("expected a list item");
+        };
+        assert_eq!(
+            item.tokens[0],
+            Token::Block(make_block(
+                "task",
+                make_meta([("checked", "true".to_string())])
+            )),
+        );
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let blocks = parse_blocks("&lt;script&gt; \\<div> & `<b>`\n\n```\n<b>\n```\n");
+        assert_eq!(
+            blocks[0].tokens,
+            vec![
+                Token::Literal("&lt;script&gt; &lt;div&gt; &amp; ".to_string()),
+                Token::Block(Block {
+                    kind: "code".to_string(),
+                    tokens: vec![Token::Literal("&lt;b&gt;".to_string())],
+                    meta: None,
+                }),
+            ],
+        );
+        // Left for the template of the block to escape.
+        assert_eq!(blocks[1].tokens, vec![Token::Literal("<b>".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_summary_marker() {
+        let input = "---\ntitle: Notes\ntimestamp: 2023-10-21T10:00:00-05:00\n---\n# Intro\n\nFirst.\n\n<!-- more -->\n\nRest.\n";
+        let markdown = parse(input).unwrap();
+        assert_eq!(markdown.summary.len(), 2);
+        assert_eq!(markdown.blocks.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_footnotes() {
+        let input = "---\ntitle: Notes\ntimestamp: 2023-10-21T10:00:00-05:00\n---\nA claim.[^1]\n\n[^1]: The source.\n";
+        let markdown = parse(input).unwrap();
+        assert_eq!(markdown.footnotes.len(), 1);
+        assert_eq!(markdown.footnotes[0].meta.as_ref().unwrap()["id"], "fn-1");
+        assert_eq!(markdown.blocks.last().unwrap().kind, "footnotes");
+    }
+
+    #[test]
+    fn test_native_only_syntax() {
+        let input = "See [[about]] for $x^2$, not $5.\n\n::: note\n{{< button >}}\n:::\n\n> ~:aside\n> Aside.\n\n`[[code]]` and:\n\n```\n$x$ ~: [[code]]\n```\n";
+        let found = native_only_syntax(input)
+            .into_iter()
+            .map(|(offset, syntax)| (&input[offset..offset + 3], syntax))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("[[a", "a wiki-style link"),
+                ("$x^", "math"),
+                (":::", "a `:::` container"),
+                ("{{<", "a shortcode"),
+                (":::", "a `:::` container"),
+                ("~:a", "a `~:` block kind"),
+            ],
+        );
+    }
+}
@@ -1,109 +1,1259 @@
 use std::collections::HashMap;
 
+use camino::{Utf8Path, Utf8PathBuf};
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take, take_until, take_while};
-use nom::character::complete::{char, newline, not_line_ending};
-use nom::combinator::{map, opt};
+use nom::bytes::complete::{
+    escaped_transform, tag, take_until, take_while, take_while1, take_while_m_n,
+};
+use nom::character::complete::{
+    char, digit1, newline, none_of, not_line_ending, one_of, space0, space1,
+};
+use nom::combinator::{eof, map, opt, recognize, value};
 use nom::multi::many0;
-use nom::sequence::delimited;
+use nom::sequence::{delimited, preceded, terminated};
 use nom::IResult;
 use thiserror::Error;
 
 use crate::common::{Block, FrontMatter, Token};
+use crate::utils::{escape_html, slugify};
 use crate::Markdown;
 
 type MarkdownResult<T> = Result<T, MarkdownError>;
 
+/// The `kind` of the placeholder `Block` left by a `<!-- more -->` marker, until `assemble`
+/// splits off the summary there.
+pub(crate) const SUMMARY_MARKER_KIND: &str = "more";
+
+/// The `kind` of a wiki-style link `Block`, until it's resolved to an `a`.
+pub(crate) const WIKILINK_KIND: &str = "wikilink";
+
+/// Points at a spot in a markdown source file, for the sake of error reporting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    // Empty until the caller fills it in with `MarkdownError::with_path`.
+    pub path: Utf8PathBuf,
+    // 1-indexed line number.
+    pub line: usize,
+    // 1-indexed column, in characters.
+    pub column: usize,
+    // The full line of source at `line`.
+    pub snippet: String,
+}
+
+impl SourceLocation {
+    /// Locate the byte `offset` within `contents`.
+    pub fn new(contents: &str, offset: usize) -> Self {
+        let offset = offset.min(contents.len());
+        let line_start = contents[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = contents[offset..]
+            .find('\n')
+            .map_or(contents.len(), |i| offset + i);
+        Self {
+            path: Utf8PathBuf::new(),
+            line: contents[..offset].matches('\n').count() + 1,
+            column: contents[line_start..offset].chars().count() + 1,
+            snippet: contents[line_start..line_end].to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.path.as_str().is_empty() {
+            write!(f, "{}:", self.path)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum MarkdownError {
     #[error("io error")]
     IoError(#[from] std::io::Error),
-    #[error("parsing error")]
-    ParseError,
+    #[error("{location}: {message}")]
+    ParseError {
+        location: SourceLocation,
+        message: String,
+    },
+    #[error("{location}: invalid {format} frontmatter: {message}")]
+    InvalidFrontMatter {
+        location: SourceLocation,
+        format: FrontMatterFormat,
+        message: String,
+    },
+    #[error("{location}: {message}")]
+    InvalidField {
+        location: SourceLocation,
+        field: String,
+        message: String,
+    },
 }
 
-/// Parse a `Markdown` struct from a `&str` of .md contents.
-pub fn parse(contents: &str) -> MarkdownResult<Markdown> {
-    let (frontmatter, offset) = parse_frontmatter(contents)?;
-    let blocks = parse_blocks(&contents[offset..]);
-    Ok(Markdown {
+impl MarkdownError {
+    /// Where in the source the error occurred, if known.
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            MarkdownError::IoError(_) => None,
+            MarkdownError::ParseError { location, .. }
+            | MarkdownError::InvalidFrontMatter { location, .. }
+            | MarkdownError::InvalidField { location, .. } => Some(location),
+        }
+    }
+
+    /// Attribute the error to the file at `path`.
+    pub fn with_path(mut self, path: &Utf8Path) -> Self {
+        match &mut self {
+            MarkdownError::IoError(_) => {}
+            MarkdownError::ParseError { location, .. }
+            | MarkdownError::InvalidFrontMatter { location, .. }
+            | MarkdownError::InvalidField { location, .. } => location.path = path.to_owned(),
+        }
+        self
+    }
+}
+
+/// Parse a `Markdown` struct from the `body` of a .md file, i.e. whatever follows its already
+/// parsed `frontmatter`.
+pub fn parse_body(frontmatter: FrontMatter, body: &str) -> Markdown {
+    assemble(frontmatter, parse_blocks(body))
+}
+
+/// Put together a `Markdown` from freshly parsed `blocks`, gathering up any footnotes and the
+/// summary.
+pub(crate) fn assemble(frontmatter: FrontMatter, mut blocks: Vec<Block>) -> Markdown {
+    // Footnotes go first, so that references in the summary are numbered too, and definitions
+    // above the marker don't end up in the summary.
+    let footnotes = collect_footnotes(&mut blocks);
+    if !footnotes.is_empty() {
+        blocks.push(Block {
+            kind: "footnotes".to_string(),
+            tokens: footnotes.iter().cloned().map(Token::Block).collect(),
+            meta: None,
+        });
+    }
+    let summary = collect_summary(&frontmatter, &mut blocks);
+    Markdown {
         frontmatter,
         blocks,
+        summary,
+        footnotes,
+        links: vec![],
+    }
+}
+
+/// Take the summary from the `summary` in the `frontmatter` if there is one, or else whatever
+/// precedes a `<!-- more -->` marker, which gets dropped from the `blocks`.
+/// Failing both, it's just the first paragraph.
+fn collect_summary(frontmatter: &FrontMatter, blocks: &mut Vec<Block>) -> Vec<Block> {
+    let mut marked = None;
+    for i in 0..blocks.len() {
+        if blocks[i].kind == SUMMARY_MARKER_KIND {
+            blocks.remove(i);
+            marked = Some(blocks[..i].to_vec());
+            break;
+        }
+        if let Some(cut) = take_summary_marker(&mut blocks[i]) {
+            let mut summary = blocks[..i].to_vec();
+            summary.extend((!cut.tokens.is_empty()).then_some(cut));
+            marked = Some(summary);
+            break;
+        }
+    }
+    if let Some(summary) = &frontmatter.summary {
+        return parse_blocks(summary);
+    }
+    marked.unwrap_or_else(|| {
+        blocks
+            .iter()
+            .find(|block| block.kind == "p")
+            .cloned()
+            .into_iter()
+            .collect()
     })
 }
 
-/// Parse the `FrontMatter` section of the contents.
+/// Drop a `<!-- more -->` marker nested somewhere within the `block`, like in a blockquote,
+/// returning a copy of the block cut short at the marker if there was one.
+fn take_summary_marker(block: &mut Block) -> Option<Block> {
+    for i in 0..block.tokens.len() {
+        let Token::Block(nested) = &mut block.tokens[i] else {
+            continue;
+        };
+        let cut = if nested.kind == SUMMARY_MARKER_KIND {
+            block.tokens.remove(i);
+            None
+        } else if let Some(cut) = take_summary_marker(nested) {
+            Some(cut)
+        } else {
+            continue;
+        };
+        let mut tokens = block.tokens[..i].to_vec();
+        tokens.extend(cut.filter(|cut| !cut.tokens.is_empty()).map(Token::Block));
+        return Some(Block {
+            kind: block.kind.clone(),
+            tokens,
+            meta: block.meta.clone(),
+        });
+    }
+    None
+}
+
+/// The formats frontmatter may be written in, told apart by how it opens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontMatterFormat {
+    /// Delimited by `---`.
+    Yaml,
+    /// Delimited by `+++`, as in Hugo.
+    Toml,
+    /// A leading JSON object.
+    Json,
+}
+
+impl std::fmt::Display for FrontMatterFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FrontMatterFormat::Yaml => "YAML",
+            FrontMatterFormat::Toml => "TOML",
+            FrontMatterFormat::Json => "JSON",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FrontMatterFormat {
+    /// Parse the `raw` frontmatter into a YAML mapping, whatever its format.
+    /// On failure, returns the offset within `raw` at fault along with the message.
+    fn parse(&self, raw: &str) -> Result<serde_yaml::Mapping, (usize, String)> {
+        match self {
+            FrontMatterFormat::Yaml => serde_yaml::from_str(raw)
+                .map_err(|e| (e.location().map_or(0, |l| l.index()), e.to_string())),
+            FrontMatterFormat::Toml => toml::from_str(raw)
+                .map(|table: toml::Table| {
+                    table
+                        .into_iter()
+                        .map(|(k, v)| (k.into(), toml_to_yaml(v)))
+                        .collect()
+                })
+                .map_err(|e| {
+                    let offset = e.span().map_or(0, |span| span.start);
+                    (offset, e.message().to_string())
+                }),
+            FrontMatterFormat::Json => serde_json::from_str(raw).map_err(|e| {
+                // Lines and columns both count from 1, but an error at the very end has line 0.
+                let offset = raw
+                    .split_inclusive('\n')
+                    .take(e.line().saturating_sub(1))
+                    .map(str::len)
+                    .sum::<usize>()
+                    + e.column().saturating_sub(1);
+                (offset.min(raw.len()), e.to_string())
+            }),
+        }
+    }
+}
+
+fn toml_to_yaml(value: toml::Value) -> serde_yaml::Value {
+    match value {
+        toml::Value::String(s) => s.into(),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        // Unquoted dates & times, like a Hugo `date`, come through as their RFC 3339 text.
+        toml::Value::Datetime(dt) => dt.to_string().into(),
+        toml::Value::Array(values) => values.into_iter().map(toml_to_yaml).collect(),
+        toml::Value::Table(table) => serde_yaml::Value::Mapping(
+            table
+                .into_iter()
+                .map(|(k, v)| (k.into(), toml_to_yaml(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Split the frontmatter off the start of the `input`, returning its format, the raw frontmatter
+/// without any delimiters and whatever follows it.
+fn split_frontmatter(input: &str) -> Option<(FrontMatterFormat, &str, &str)> {
+    fn delimited_by<'a>(delimiter: &str, input: &'a str) -> IResult<&'a str, &'a str> {
+        delimited(tag(delimiter), take_until(delimiter), tag(delimiter))(input)
+    }
+    for (format, delimiter) in [
+        (FrontMatterFormat::Yaml, "---"),
+        (FrontMatterFormat::Toml, "+++"),
+    ] {
+        if let Ok((rest, raw)) = delimited_by(delimiter, input) {
+            return Some((format, raw, rest));
+        }
+    }
+    if input.starts_with('{') {
+        // The object ends wherever JSON says it does. If it's malformed, take everything, and
+        // leave it to the actual parsing to point out what's wrong.
+        let mut objects =
+            serde_json::Deserializer::from_str(input).into_iter::<serde::de::IgnoredAny>();
+        let end = match objects.next() {
+            Some(Ok(_)) => objects.byte_offset(),
+            _ => input.len(),
+        };
+        return Some((FrontMatterFormat::Json, &input[..end], &input[end..]));
+    }
+    None
+}
+
+/// Parse the `FrontMatter` section of the contents, which may be YAML, TOML or JSON.
 pub fn parse_frontmatter(contents: &str) -> MarkdownResult<(FrontMatter, usize)> {
-    fn parse(input: &str) -> IResult<&str, FrontMatter> {
-        let (input, _) = many0(newline)(input)?;
-        let (input, frontmatter_raw) = delimited(tag("---"), take_until("---"), tag("---"))(input)?;
-        let (input, _) = many0(newline)(input)?;
-        // TODO lightly gross to first allocate the `HashMap` just to try to supply some programmatic
-        // defaults to the `FrontMatter` struct. Maybe there's a better way
-        let frontmatter: HashMap<&str, &str> = serde_yaml::from_str(frontmatter_raw).unwrap();
-        Ok((input, frontmatter.try_into().unwrap()))
+    let input = contents.trim_start_matches('\n');
+    let start = contents.len() - input.len();
+    let (format, frontmatter_raw, remaining) =
+        split_frontmatter(input).ok_or_else(|| MarkdownError::ParseError {
+            location: SourceLocation::new(contents, 0),
+            message: "expected frontmatter delimited by `---` or `+++`, or a JSON object"
+                .to_string(),
+        })?;
+    // `frontmatter_raw` is a slice of `contents`, so we can recover where it starts.
+    let raw_offset = frontmatter_raw.as_ptr() as usize - contents.as_ptr() as usize;
+
+    let kvs = format.parse(frontmatter_raw).map_err(|(offset, message)| {
+        MarkdownError::InvalidFrontMatter {
+            location: SourceLocation::new(contents, raw_offset + offset),
+            format,
+            message,
+        }
+    })?;
+    let frontmatter = FrontMatter::try_from(kvs).map_err(|e| {
+        // Point at the offending value if there is one, otherwise at the frontmatter itself.
+        let offset = find_frontmatter_value(frontmatter_raw, format, e.field())
+            .map_or(start, |offset| raw_offset + offset);
+        MarkdownError::InvalidField {
+            location: SourceLocation::new(contents, offset),
+            field: e.field().to_string(),
+            message: e.to_string(),
+        }
+    })?;
+    let remaining = remaining.trim_start_matches('\n');
+    Ok((frontmatter, contents.len() - remaining.len()))
+}
+
+/// Find the offset of the value of the top-level `field` within raw frontmatter.
+fn find_frontmatter_value(
+    frontmatter_raw: &str,
+    format: FrontMatterFormat,
+    field: &str,
+) -> Option<usize> {
+    let quoted = format!("\"{}\"", field);
+    let (keys, separator): (&[&str], char) = match format {
+        FrontMatterFormat::Yaml => (&[field], ':'),
+        FrontMatterFormat::Toml => (&[field, &quoted], '='),
+        FrontMatterFormat::Json => (&[&quoted], ':'),
+    };
+    let mut offset = 0;
+    for line in frontmatter_raw.split_inclusive('\n') {
+        // Indentation means nesting in YAML, but not in the others.
+        let key_part = match format {
+            FrontMatterFormat::Yaml => line,
+            _ => line.trim_start(),
+        };
+        for key in keys {
+            if let Some(value) = key_part
+                .strip_prefix(key)
+                .and_then(|rest| rest.trim_start().strip_prefix(separator))
+            {
+                return Some(offset + line.len() - value.trim_start().len());
+            }
+        }
+        offset += line.len();
     }
-    let total_len = contents.len();
-    let (remaining, frontmatter) = parse(contents).map_err(|_| MarkdownError::ParseError)?;
-    let offset = total_len - remaining.len();
-    Ok((frontmatter, offset))
+    None
 }
 
 /// Parse out the `body` of the post, which is composed of `Block`s.
 pub fn parse_blocks(contents: &str) -> Vec<Block> {
-    contents.trim().split("\n\n").map(parse_block).collect()
+    parse_spaced_blocks(contents).0
 }
 
-/// Parse a single chunk of text into a `Block`.
-fn parse_block(content: &str) -> Block {
-    // TODO nested block support, like links etc.
-    let (content, kind) = opt(parse_kind)(content).unwrap();
+/// Like `parse_blocks`, but also tells whether any two of the blocks are separated by blank
+/// lines, which is what makes the contents of a list item loose.
+fn parse_spaced_blocks(contents: &str) -> (Vec<Block>, bool) {
+    let mut blocks = vec![];
+    let mut spaced = false;
+    let mut input = skip_blank_lines(contents.trim_end());
+    while !input.is_empty() {
+        let (rest, block) = alt((
+            parse_summary_marker,
+            parse_fenced_code,
+            parse_display_math,
+            parse_container,
+            parse_blockquote,
+            parse_table,
+            parse_thematic_break,
+            parse_list,
+            parse_footnote_definition,
+            parse_paragraph,
+        ))(input)
+        .unwrap();
+        blocks.push(block);
+        input = skip_blank_lines(rest);
+        spaced |= !input.is_empty() && input.len() < rest.len();
+    }
+    (blocks, spaced)
+}
+
+/// Drop any leading lines that consist only of whitespace.
+fn skip_blank_lines(mut input: &str) -> &str {
+    while let Some((line, rest)) = split_first_line(input) {
+        if !line.trim().is_empty() {
+            break;
+        }
+        input = rest;
+    }
+    input
+}
+
+/// Split off the first line (without its newline) from the rest of the `input`.
+fn split_first_line(input: &str) -> Option<(&str, &str)> {
+    if input.is_empty() {
+        return None;
+    }
+    Some(match input.find('\n') {
+        Some(i) => (&input[..i], &input[i + 1..]),
+        None => (input, ""),
+    })
+}
+
+/// Whether the `line` starts a block that may cut a paragraph short.
+fn interrupts_paragraph(line: &str) -> bool {
+    // Ordered lists only get to interrupt if they start at 1, so that a wrapped line that happens
+    // to start with a number isn't mistaken for a list.
+    parse_fence(line).is_ok()
+        || is_summary_marker(line)
+        || is_thematic_break(line)
+        || parse_container_fence(line).is_ok()
+        || parse_blockquote_marker(line).is_ok()
+        || matches!(
+            parse_list_marker(line.trim_start()),
+            Ok((_, None)) | Ok((_, Some(1)))
+        )
+}
+
+/// Measure the leading whitespace of the `line`, counting tabs as 4 spaces.
+fn indentation(line: &str) -> (usize, &str) {
+    let trimmed = line.trim_start_matches([' ', '\t']);
+    let width = line[..line.len() - trimmed.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    (width, trimmed)
+}
+
+/// Strip up to `width` columns of leading whitespace from the `line`, counting tabs as 4.
+fn strip_indentation(line: &str, width: usize) -> &str {
+    let mut stripped = 0;
+    for (i, c) in line.char_indices() {
+        stripped += match c {
+            ' ' => 1,
+            '\t' => 4,
+            _ => return &line[i..],
+        };
+        if stripped > width {
+            return &line[i..];
+        }
+    }
+    ""
+}
+
+/// Whether the `line` is a thematic break, i.e. three or more of the same `-`, `*` or `_`,
+/// optionally spaced out.
+fn is_thematic_break(line: &str) -> bool {
+    let (indent, trimmed) = indentation(line);
+    let Some(c @ ('-' | '*' | '_')) = trimmed.chars().next() else {
+        return false;
+    };
+    indent < 4
+        && trimmed.chars().filter(|&other| other == c).count() >= 3
+        && trimmed
+            .chars()
+            .all(|other| other == c || other == ' ' || other == '\t')
+}
+
+/// Parse a thematic break, like `---` or `* * *`, into an `hr`.
+fn parse_thematic_break(input: &str) -> IResult<&str, Block> {
+    match split_first_line(input) {
+        Some((line, rest)) if is_thematic_break(line) => Ok((
+            rest,
+            Block {
+                kind: "hr".to_string(),
+                tokens: vec![],
+                meta: None,
+            },
+        )),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        ))),
+    }
+}
+
+/// Consume lines up until a blank line, or the start of some other kind of block.
+fn parse_paragraph(input: &str) -> IResult<&str, Block> {
+    let mut end = 0;
+    let mut rest = input;
+    while let Some((line, next)) = split_first_line(rest) {
+        if line.trim().is_empty() || (end > 0 && interrupts_paragraph(line)) {
+            break;
+        }
+        end = input.len() - next.len();
+        rest = next;
+    }
+    if end == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::TakeUntil,
+        )));
+    }
+    Ok((rest, parse_block(input[..end].trim_end())))
+}
+
+/// Parse the `>` that marks a line as quoted, along with one optional following space.
+fn parse_blockquote_marker(input: &str) -> IResult<&str, ()> {
+    let (input, _) = take_while_m_n(0, 3, |c| c == ' ')(input)?;
+    let (input, _) = char('>')(input)?;
+    let (input, _) = opt(char(' '))(input)?;
+    Ok((input, ()))
+}
+
+/// Parse a blockquote, whose contents are themselves parsed as a sequence of `Block`s.
+fn parse_blockquote(input: &str) -> IResult<&str, Block> {
+    parse_blockquote_marker(input)?;
+    let mut lines = vec![];
+    let mut rest = input;
+    while let Some((line, next)) = split_first_line(rest) {
+        match parse_blockquote_marker(line) {
+            Ok((quoted, _)) => lines.push(quoted),
+            // Unquoted lines lazily continue the quote until a blank line or another block.
+            Err(_) if line.trim().is_empty() || interrupts_paragraph(line) => break,
+            Err(_) => lines.push(line),
+        }
+        rest = next;
+    }
+    let tokens = parse_blocks(&lines.join("\n"))
+        .into_iter()
+        .map(Token::Block)
+        .collect();
+    Ok((
+        rest,
+        Block {
+            kind: "blockquote".to_string(),
+            tokens,
+            meta: None,
+        },
+    ))
+}
+
+/// Make a `math` block of the LaTeX `source`, to be converted to MathML later on.
+fn make_math_block(source: &str, display: bool) -> Block {
+    let display = if display { "block" } else { "inline" };
     Block {
-        kind: kind.map_or("p".to_string(), |k| k.to_string()),
-        tokens: parse_inner(content),
-        meta: None,
+        kind: "math".to_string(),
+        tokens: vec![Token::Literal(source.trim().to_string())],
+        meta: Some(HashMap::from([(
+            "display".to_string(),
+            display.to_string(),
+        )])),
     }
 }
 
-/// Try parsing non-default `Block` `kind`s, which determine the rendering of the block.
-fn parse_kind(input: &str) -> IResult<&str, &str> {
-    let (input, kind) = alt((
-        map(tag("# "), |_| "h1"),
-        map(tag("## "), |_| "h2"),
-        map(tag("### "), |_| "h3"),
-        map(tag("#### "), |_| "h4"),
-        map(tag("##### "), |_| "h5"),
-        map(tag("###### "), |_| "h6"),
-        |input| {
-            // Custom block kinds are declared like `~kind`.
-            let (input, _) = tag("~:")(input)?;
-            // TODO this shouldn't have any spaces in it, either.
-            let (input, kind) = not_line_ending(input)?;
-            let (input, _) = newline(input)?;
-            Ok((input, kind))
+/// Parse a `$$ ... $$` display math block, which may span several lines.
+fn parse_display_math(input: &str) -> IResult<&str, Block> {
+    let (input, _) = take_while_m_n(0, 3, |c| c == ' ')(input)?;
+    let (input, _) = tag("$$")(input)?;
+    let (input, source) = take_until("$$")(input)?;
+    let (input, _) = tag("$$")(input)?;
+    // Anything trailing the closing `$$` makes it inline math in a paragraph instead.
+    let (input, _) = space0(input)?;
+    let (input, _) = alt((eof, map(newline, |_| "")))(input)?;
+    Ok((input, make_math_block(source, true)))
+}
+
+/// Whether the `line` is a `<!-- more -->` marker, separating the summary from the rest.
+pub(crate) fn is_summary_marker(line: &str) -> bool {
+    line.trim()
+        .strip_prefix("<!--")
+        .and_then(|line| line.strip_suffix("-->"))
+        .is_some_and(|comment| comment.trim().eq_ignore_ascii_case("more"))
+}
+
+/// Parse a `<!-- more -->` marker line.
+fn parse_summary_marker(input: &str) -> IResult<&str, Block> {
+    match split_first_line(input) {
+        Some((line, rest)) if is_summary_marker(line) => Ok((
+            rest,
+            Block {
+                kind: SUMMARY_MARKER_KIND.to_string(),
+                tokens: vec![],
+                meta: None,
+            },
+        )),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        ))),
+    }
+}
+
+/// Parse the `:::` that opens or closes a container directive.
+fn parse_container_fence(input: &str) -> IResult<&str, &str> {
+    preceded(
+        take_while_m_n(0, 3, |c| c == ' '),
+        take_while_m_n(3, usize::MAX, |c| c == ':'),
+    )(input)
+}
+
+/// Parse the opening line of a container directive, like `:::kind key=value`.
+fn parse_container_open(input: &str) -> IResult<&str, BlockKind<'_>> {
+    let (input, _) = parse_container_fence(input)?;
+    let (input, _) = space0(input)?;
+    let (input, kind) = take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')(input)?;
+    let (input, args) = many0(preceded(space1, parse_block_arg))(input)?;
+    let (input, _) = space0(input)?;
+    let meta = (!args.is_empty()).then(|| args.into_iter().collect());
+    Ok((input, (kind, meta)))
+}
+
+/// Whether the `line` is a bare `:::`, closing the innermost open container.
+fn is_container_close(line: &str) -> bool {
+    matches!(parse_container_fence(line), Ok((rest, _)) if rest.trim().is_empty())
+}
+
+/// Parse a container directive, which wraps any number of `Block`s, up until a matching `:::`.
+/// Containers may be nested, and an unclosed container runs until the end of the `input`.
+fn parse_container(input: &str) -> IResult<&str, Block> {
+    let (body, (kind, meta)) = parse_container_open(input)?;
+    let (body, _) = alt((eof, map(newline, |_| "")))(body)?;
+    let mut depth = 1;
+    let mut rest = body;
+    let mut end = body.len();
+    while let Some((line, next)) = split_first_line(rest) {
+        // Don't go looking for closing fences inside of code.
+        if parse_fence(line).is_ok() {
+            rest = parse_fenced_code(rest)?.0;
+            continue;
+        }
+        if is_container_close(line) {
+            depth -= 1;
+            if depth == 0 {
+                end = body.len() - rest.len();
+                rest = next;
+                break;
+            }
+        } else if parse_container_open(line).is_ok() {
+            depth += 1;
+        }
+        rest = next;
+    }
+    if depth > 0 {
+        end = body.len();
+    }
+    let tokens = parse_blocks(&body[..end])
+        .into_iter()
+        .map(Token::Block)
+        .collect();
+    Ok((
+        rest,
+        Block {
+            kind: kind.to_string(),
+            tokens,
+            meta,
         },
+    ))
+}
+
+/// Parse a footnote definition like `[^label]: text`, which runs until the next blank line.
+fn parse_footnote_definition(input: &str) -> IResult<&str, Block> {
+    let (input, label) = parse_footnote_label(input)?;
+    let (input, _) = char(':')(input)?;
+    let (rest, block) = parse_paragraph(input.trim_start_matches([' ', '\t']))?;
+    Ok((
+        rest,
+        Block {
+            kind: "footnote".to_string(),
+            tokens: block.tokens,
+            meta: Some(HashMap::from([("label".to_string(), label.to_string())])),
+        },
+    ))
+}
+
+/// Pull the footnote definitions out of `blocks`, and number them by the order in which they are
+/// first referenced. References to undefined footnotes are left as they were written.
+/// Definitions are expected to be `footnote` blocks and references `footnote_ref` blocks,
+/// each with a `label` in their `meta`.
+fn collect_footnotes(blocks: &mut Vec<Block>) -> Vec<Block> {
+    let (definitions, rest): (Vec<Block>, Vec<Block>) =
+        blocks.drain(..).partition(|block| block.kind == "footnote");
+    *blocks = rest;
+
+    let mut definitions: HashMap<String, Block> = definitions
+        .into_iter()
+        .filter_map(|block| {
+            let label = block.meta.as_ref()?.get("label")?.clone();
+            Some((label, block))
+        })
+        .collect();
+    let mut footnotes = vec![];
+    let mut ref_counts: HashMap<String, usize> = HashMap::new();
+    for block in blocks.iter_mut() {
+        number_footnote_refs(block, &mut definitions, &mut footnotes, &mut ref_counts);
+    }
+    footnotes
+}
+
+fn number_footnote_refs(
+    block: &mut Block,
+    definitions: &mut HashMap<String, Block>,
+    footnotes: &mut Vec<Block>,
+    ref_counts: &mut HashMap<String, usize>,
+) {
+    for token in block.tokens.iter_mut() {
+        let Token::Block(nested) = token else {
+            continue;
+        };
+        if nested.kind != "footnote_ref" {
+            number_footnote_refs(nested, definitions, footnotes, ref_counts);
+            continue;
+        }
+        let meta = nested.meta.get_or_insert_with(HashMap::new);
+        let label = meta.get("label").cloned().unwrap_or_default();
+        let id = slugify(&label);
+
+        if let Some(mut definition) = definitions.remove(&label) {
+            definition.meta = Some(HashMap::from([
+                ("label".to_string(), label.clone()),
+                ("number".to_string(), (footnotes.len() + 1).to_string()),
+                ("id".to_string(), format!("fn-{}", id)),
+                ("backref".to_string(), format!("#fnref-{}", id)),
+            ]));
+            footnotes.push(definition);
+        }
+        let Some(footnote) = footnotes
+            .iter()
+            .find(|footnote| footnote.meta.as_ref().and_then(|m| m.get("label")) == Some(&label))
+        else {
+            *token = Token::Literal(format!("[^{}]", escape_html(&label)));
+            continue;
+        };
+
+        let count = ref_counts.entry(label.clone()).or_default();
+        *count += 1;
+        let footnote_meta = footnote.meta.as_ref().unwrap();
+        meta.insert("number".to_string(), footnote_meta["number"].clone());
+        meta.insert("href".to_string(), format!("#{}", footnote_meta["id"]));
+        meta.insert(
+            "id".to_string(),
+            match count {
+                1 => format!("fnref-{}", id),
+                n => format!("fnref-{}-{}", id, n),
+            },
+        );
+    }
+}
+
+/// Split a table row into its cells, ignoring any leading & trailing pipes.
+fn split_table_row(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+    let mut cells = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '|' if !escaped => {
+                cells.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    cells.push(line[start..].trim());
+    cells
+}
+
+/// Parse the alignments out of a table's delimiter row, e.g. `| :--- | :---: | ---: |`.
+fn parse_table_alignments(line: &str) -> Option<Vec<Option<&'static str>>> {
+    if !line.contains('-') {
+        return None;
+    }
+    split_table_row(line)
+        .into_iter()
+        .map(|cell| {
+            let left = cell.starts_with(':');
+            let right = cell.ends_with(':');
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => Some("center"),
+                (true, false) => Some("left"),
+                (false, true) => Some("right"),
+                (false, false) => None,
+            })
+        })
+        .collect()
+}
+
+/// Make a `tr` out of a table row, padding or truncating it to fit the `alignments`.
+fn build_table_row(line: &str, cell_kind: &str, alignments: &[Option<&str>]) -> Token {
+    let mut cells = split_table_row(line).into_iter();
+    let tokens = alignments
+        .iter()
+        .map(|alignment| {
+            Token::Block(Block {
+                kind: cell_kind.to_string(),
+                // Escaped pipes only needed escaping to avoid splitting the row.
+                tokens: parse_inner(&cells.next().unwrap_or("").replace("\\|", "|")),
+                meta: alignment
+                    .map(|align| HashMap::from([("align".to_string(), align.to_string())])),
+            })
+        })
+        .collect();
+    Token::Block(Block {
+        kind: "tr".to_string(),
+        tokens,
+        meta: None,
+    })
+}
+
+/// Parse a GitHub-flavored table: a header row, a delimiter row, then any number of body rows.
+fn parse_table(input: &str) -> IResult<&str, Block> {
+    let fail = || nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify));
+    let (header, rest) = split_first_line(input).ok_or_else(fail)?;
+    let (delimiter, mut rest) = split_first_line(rest).ok_or_else(fail)?;
+    if !header.contains('|') {
+        return Err(fail());
+    }
+    let alignments = parse_table_alignments(delimiter).ok_or_else(fail)?;
+    if split_table_row(header).len() != alignments.len() {
+        return Err(fail());
+    }
+
+    let mut rows = vec![];
+    while let Some((line, next)) = split_first_line(rest) {
+        if line.trim().is_empty() || interrupts_paragraph(line) {
+            break;
+        }
+        rows.push(build_table_row(line, "td", &alignments));
+        rest = next;
+    }
+
+    let mut tokens = vec![Token::Block(Block {
+        kind: "thead".to_string(),
+        tokens: vec![build_table_row(header, "th", &alignments)],
+        meta: None,
+    })];
+    if !rows.is_empty() {
+        tokens.push(Token::Block(Block {
+            kind: "tbody".to_string(),
+            tokens: rows,
+            meta: None,
+        }));
+    }
+    Ok((
+        rest,
+        Block {
+            kind: "table".to_string(),
+            tokens,
+            meta: None,
+        },
+    ))
+}
+
+/// Parse a list item marker, returning the item number for ordered lists.
+fn parse_list_marker(input: &str) -> IResult<&str, Option<u32>> {
+    let (input, number) = alt((
+        map(one_of("-*+"), |_| None),
+        map(terminated(digit1, one_of(".)")), |n: &str| n.parse().ok()),
     ))(input)?;
-    Ok((input, kind))
+    let (input, _) = alt((space1, eof))(input)?;
+    Ok((input, number))
 }
 
-fn parse_nested_special_token<'a>(
-    delimiter: char,
-    kind: &'a str,
-) -> impl nom::Parser<&'a str, Token, nom::error::Error<&'a str>> {
-    move |input: &'a str| {
-        let (input, _) = char(delimiter)(input)?;
-        let (input, content) = take_while(|c| c != delimiter)(input)?;
-        let tokens = parse_inner(content);
-        let (input, _) = char(delimiter)(input)?;
-        Ok((
-            input,
+/// An item of a list under construction.
+struct ListItem<'a> {
+    // The number of the item in ordered lists.
+    number: Option<u32>,
+    // The width of the indentation that the contents of the item line up with.
+    content_indent: usize,
+    // The contents of the item, with that indentation stripped.
+    lines: Vec<&'a str>,
+    // The fence of a code block left open by the `lines` so far, which can't be lazily continued.
+    fence: Option<&'a str>,
+}
+
+impl<'a> ListItem<'a> {
+    fn push(&mut self, line: &'a str) {
+        self.fence = match self.fence {
+            Some(fence) if closes_fence(line, fence) => None,
+            Some(fence) => Some(fence),
+            None => parse_fence(line).ok().map(|(_, (fence, _))| fence),
+        };
+        self.lines.push(line);
+    }
+}
+
+/// Parse a (possibly nested) list, consuming any lines that belong to its items.
+/// Lines indented past the marker of an item make up its contents, which are themselves parsed
+/// as a sequence of `Block`s, the way a blockquote's are.
+fn parse_list(input: &str) -> IResult<&str, Block> {
+    let (first_line, _) = split_first_line(input).unwrap_or_default();
+    let (base_indent, trimmed) = indentation(first_line);
+    let (_, first_number) = parse_list_marker(trimmed)?;
+    let ordered = first_number.is_some();
+
+    let mut items: Vec<ListItem> = vec![];
+    // Blank lines between items, or between the blocks within an item, make for a loose list.
+    let mut loose = false;
+    let mut after_blank = false;
+    let mut rest = input;
+    while let Some((line, next)) = split_first_line(rest) {
+        let (indent, trimmed) = indentation(line);
+        if trimmed.is_empty() {
+            // A blank line only continues the list if it's followed by more of the list.
+            let following = skip_blank_lines(next);
+            match split_first_line(following) {
+                Some((line, _))
+                    if indentation(line).0 > base_indent
+                        || matches!(
+                            parse_list_marker(indentation(line).1),
+                            Ok((_, number)) if number.is_some() == ordered
+                        ) =>
+                {
+                    if let Some(item) = items.last_mut() {
+                        item.push("");
+                    }
+                    after_blank = true;
+                    rest = next;
+                    continue;
+                }
+                _ => break,
+            }
+        }
+        match items.last_mut() {
+            // Anything indented past the marker belongs to the current item, nested lists included.
+            Some(item) if indent > base_indent => {
+                item.push(strip_indentation(line, item.content_indent))
+            }
+            _ if is_thematic_break(line) => break,
+            _ => match parse_list_marker(trimmed) {
+                // Switching between ordered and unordered items starts a new list.
+                Ok((_, number)) if number.is_some() != ordered => break,
+                Ok((text, number)) => {
+                    loose |= after_blank;
+                    let mut item = ListItem {
+                        number,
+                        content_indent: indent + trimmed.len() - text.len(),
+                        lines: vec![],
+                        fence: None,
+                    };
+                    item.push(text);
+                    items.push(item);
+                }
+                // Otherwise it's a lazy continuation of the paragraph the item leaves off with.
+                Err(_) => match items.last_mut() {
+                    Some(item)
+                        if !after_blank && item.fence.is_none() && !interrupts_paragraph(line) =>
+                    {
+                        item.push(trimmed)
+                    }
+                    _ => break,
+                },
+            },
+        }
+        after_blank = false;
+        rest = next;
+    }
+
+    let contents: Vec<(Vec<Block>, bool)> = items
+        .iter()
+        .map(|item| parse_spaced_blocks(&item.lines.join("\n")))
+        .collect();
+    loose |= contents.iter().any(|(_, spaced)| *spaced);
+    let tokens = contents
+        .into_iter()
+        .map(|(blocks, _)| {
+            let tokens = blocks
+                .into_iter()
+                .flat_map(|block| match block.kind.as_str() {
+                    // The paragraphs of tight lists go without a `p` of their own.
+                    "p" if !loose => block.tokens,
+                    _ => vec![Token::Block(block)],
+                })
+                .collect();
             Token::Block(Block {
-                kind: kind.to_string(),
+                kind: "li".to_string(),
                 tokens,
                 meta: None,
-            }),
-        ))
+            })
+        })
+        .collect();
+
+    let (kind, meta) = match items.first().and_then(|item| item.number) {
+        None => ("ul", None),
+        Some(1) => ("ol", None),
+        Some(n) => (
+            "ol",
+            Some(HashMap::from([("start".to_string(), n.to_string())])),
+        ),
+    };
+    Ok((
+        rest,
+        Block {
+            kind: kind.to_string(),
+            tokens,
+            meta,
+        },
+    ))
+}
+
+/// Parse the opening of a code fence, returning the fence itself and the info string.
+fn parse_fence(input: &str) -> IResult<&str, (&str, &str)> {
+    let (input, _) = take_while_m_n(0, 3, |c| c == ' ')(input)?;
+    let (input, fence) = alt((
+        take_while_m_n(3, usize::MAX, |c| c == '`'),
+        take_while_m_n(3, usize::MAX, |c| c == '~'),
+    ))(input)?;
+    let (input, info) = not_line_ending(input)?;
+    // Backtick fences can't have backticks in the info string, lest they be confused with
+    // inline code.
+    if fence.starts_with('`') && info.contains('`') {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
     }
+    Ok((input, (fence, info.trim())))
+}
+
+/// Whether the `line` closes a code block opened by the `fence`.
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let closing = line.trim();
+    closing.len() >= fence.len() && closing.chars().all(|c| fence.starts_with(c))
+}
+
+/// Parse a fenced code block, retaining its contents verbatim.
+/// An unclosed fence runs until the end of the `input`.
+fn parse_fenced_code(input: &str) -> IResult<&str, Block> {
+    let indent = input.len() - input.trim_start_matches(' ').len();
+    let (input, (fence, info)) = parse_fence(input)?;
+    let mut rest = input.strip_prefix('\n').unwrap_or(input);
+    let mut lines = vec![];
+    while let Some((line, next)) = split_first_line(rest) {
+        rest = next;
+        if closes_fence(line, fence) {
+            break;
+        }
+        // Strip up to as much indentation as the opening fence had.
+        let strip = line.len() - line.trim_start_matches(' ').len();
+        lines.push(&line[strip.min(indent)..]);
+    }
+
+    let meta = info
+        .split_whitespace()
+        .next()
+        .map(|language| HashMap::from([("language".to_string(), language.to_string())]));
+    Ok((
+        rest,
+        Block {
+            kind: "pre".to_string(),
+            tokens: vec![Token::Literal(lines.join("\n"))],
+            meta,
+        },
+    ))
+}
+
+/// Parse a single chunk of text into a `Block`.
+fn parse_block(content: &str) -> Block {
+    // TODO nested block support, like links etc.
+    let (content, kind) = opt(parse_kind)(content).unwrap();
+    let (kind, meta) = kind.unwrap_or(("p", None));
+    let tokens = parse_inner(content);
+    // Shortcodes on their own are most likely embeds, which shouldn't end up inside a `<p>`.
+    if kind == "p"
+        && let [Token::Block(block)] = tokens.as_slice()
+        && block.kind == "shortcode"
+    {
+        return block.clone();
+    }
+    Block {
+        kind: kind.to_string(),
+        tokens,
+        meta,
+    }
+}
+
+/// A `Block`'s `kind`, along with any `meta` declared with it.
+type BlockKind<'a> = (&'a str, Option<HashMap<String, String>>);
+
+/// Parse a custom block argument value, either bare or double quoted with `\"` escapes.
+/// Bare values stop short of any `>`, so that they can't run into the end of a shortcode.
+fn parse_block_arg_value(input: &str) -> IResult<&str, String> {
+    alt((
+        delimited(
+            char('"'),
+            map(
+                opt(escaped_transform(
+                    none_of("\\\""),
+                    '\\',
+                    alt((value("\\", char('\\')), value("\"", char('"')))),
+                )),
+                Option::unwrap_or_default,
+            ),
+            char('"'),
+        ),
+        map(
+            take_while1(|c: char| !c.is_whitespace() && c != '"' && c != '>'),
+            String::from,
+        ),
+    ))(input)
+}
+
+/// Parse a `key=value` argument to a custom block kind. A bare `key` is taken as a flag.
+fn parse_block_arg(input: &str) -> IResult<&str, (String, String)> {
+    let (input, key) = take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')(input)?;
+    let (input, value) = opt(preceded(char('='), parse_block_arg_value))(input)?;
+    Ok((
+        input,
+        (key.to_string(), value.unwrap_or_else(|| "true".to_string())),
+    ))
+}
+
+/// Parse a custom block kind declared like `~:kind key=value key="quoted value"`, with any
+/// arguments going into the `Block`'s `meta`.
+fn parse_custom_kind(input: &str) -> IResult<&str, BlockKind<'_>> {
+    let (input, _) = tag("~:")(input)?;
+    let (input, kind) = take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')(input)?;
+    let (input, args) = many0(preceded(space1, parse_block_arg))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = newline(input)?;
+    let meta = (!args.is_empty()).then(|| args.into_iter().collect());
+    Ok((input, (kind, meta)))
+}
+
+/// Try parsing non-default `Block` `kind`s, which determine the rendering of the block.
+fn parse_kind(input: &str) -> IResult<&str, BlockKind<'_>> {
+    alt((
+        map(tag("# "), |_| ("h1", None)),
+        map(tag("## "), |_| ("h2", None)),
+        map(tag("### "), |_| ("h3", None)),
+        map(tag("#### "), |_| ("h4", None)),
+        map(tag("##### "), |_| ("h5", None)),
+        map(tag("###### "), |_| ("h6", None)),
+        parse_custom_kind,
+    ))(input)
+}
+
+/// Parse a shortcode like `{{< name key=value >}}`, which renders the
+/// `blocks/shortcodes/<name>.liquid` partial with the given arguments.
+fn parse_shortcode(input: &str) -> IResult<&str, Token> {
+    let (input, _) = tag("{{<")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, name) = take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')(input)?;
+    let (input, args) = many0(preceded(space1, parse_block_arg))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = tag(">}}")(input)?;
+    // The name goes in under `shortcode` so as not to clash with any of the arguments.
+    let mut meta: HashMap<String, String> = args.into_iter().collect();
+    meta.insert("shortcode".to_string(), name.to_string());
+    Ok((
+        input,
+        Token::Block(Block {
+            kind: "shortcode".to_string(),
+            tokens: vec![],
+            meta: Some(meta),
+        }),
+    ))
+}
+
+/// Check that every shortcode in the `blocks` has a template, per `exists`, pointing out the
+/// first use of any that doesn't within the source `contents`.
+pub fn check_shortcodes(
+    contents: &str,
+    blocks: &[Block],
+    exists: &impl Fn(&str) -> bool,
+) -> MarkdownResult<()> {
+    for block in blocks {
+        if block.kind == "shortcode"
+            && let Some(name) = block.meta.as_ref().and_then(|meta| meta.get("shortcode"))
+            && !exists(name)
+        {
+            let offset = contents
+                .match_indices("{{<")
+                .map(|(i, _)| i)
+                .find(|&i| {
+                    matches!(
+                        parse_shortcode(&contents[i..]),
+                        Ok((_, Token::Block(Block { meta: Some(meta), .. })))
+                            if meta.get("shortcode") == Some(name)
+                    )
+                })
+                .unwrap_or(0);
+            return Err(MarkdownError::ParseError {
+                location: SourceLocation::new(contents, offset),
+                message: format!("unknown shortcode `{}`", name),
+            });
+        }
+        for token in block.tokens.iter() {
+            if let Token::Block(nested) = token {
+                check_shortcodes(contents, std::slice::from_ref(nested), exists)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a footnote label like `[^label]`.
+fn parse_footnote_label(input: &str) -> IResult<&str, &str> {
+    delimited(
+        tag("[^"),
+        take_while1(|c: char| c != ']' && !c.is_whitespace()),
+        char(']'),
+    )(input)
+}
+
+/// Parse a reference to a footnote, which gets numbered later on in `collect_footnotes`.
+fn parse_footnote_ref(input: &str) -> IResult<&str, Token> {
+    let (input, label) = parse_footnote_label(input)?;
+    Ok((
+        input,
+        Token::Block(Block {
+            kind: "footnote_ref".to_string(),
+            tokens: vec![],
+            meta: Some(HashMap::from([("label".to_string(), label.to_string())])),
+        }),
+    ))
+}
+
+/// Parse a wiki-style link like `[[target]]` or `[[target|text]]`, where the target is the
+/// source path or the title of another page. These get resolved later on, against the whole site.
+fn parse_wikilink(input: &str) -> IResult<&str, Token> {
+    let (input, inner) =
+        delimited(tag("[["), take_while1(|c| c != ']' && c != '\n'), tag("]]"))(input)?;
+    let (target, text) = match inner.split_once('|') {
+        Some((target, text)) => (target, Some(text)),
+        None => (inner, None),
+    };
+    Ok((
+        input,
+        Token::Block(Block {
+            kind: WIKILINK_KIND.to_string(),
+            tokens: text.map(parse_inner).unwrap_or_default(),
+            meta: Some(HashMap::from([(
+                "target".to_string(),
+                target.trim().to_string(),
+            )])),
+        }),
+    ))
 }
 
 fn parse_link_tag(input: &str) -> IResult<&str, Token> {
@@ -117,7 +1267,7 @@ fn parse_link_tag(input: &str) -> IResult<&str, Token> {
         input,
         Token::Block(Block {
             kind: "a".to_string(),
-            tokens: vec![Token::Literal(content.to_string())],
+            tokens: parse_inner(content),
             meta: Some(HashMap::from_iter(vec![(
                 "href".to_string(),
                 url.to_string(),
@@ -126,43 +1276,421 @@ fn parse_link_tag(input: &str) -> IResult<&str, Token> {
     ))
 }
 
-// TODO this looks terrible, but I gues it works for now.
+/// Parse an image like `![alt](src "title")`, where the title is optional.
+fn parse_image_tag(input: &str) -> IResult<&str, Token> {
+    let (input, _) = tag("![")(input)?;
+    let (input, alt) = take_while(|c| c != ']')(input)?;
+    let (input, _) = tag("](")(input)?;
+    let (input, src) = take_while(|c: char| c != ')' && !c.is_whitespace())(input)?;
+    let (input, title) = opt(preceded(
+        space1,
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+    ))(input)?;
+    let (input, _) = char(')')(input)?;
+
+    let mut meta = HashMap::from([
+        ("src".to_string(), src.to_string()),
+        ("alt".to_string(), alt.to_string()),
+    ]);
+    if let Some(title) = title {
+        meta.insert("title".to_string(), title.to_string());
+    }
+    Ok((
+        input,
+        Token::Block(Block {
+            kind: "img".to_string(),
+            tokens: vec![],
+            meta: Some(meta),
+        }),
+    ))
+}
+
+/// Append the `token` to `tokens`, merging it into the last one if both are literals.
+fn push_token(tokens: &mut Vec<Token>, token: Token) {
+    if let (Token::Literal(literal), Some(Token::Literal(last))) = (&token, tokens.last_mut()) {
+        last.push_str(literal);
+    } else {
+        tokens.push(token);
+    }
+}
+
+/// Parse a backslash escape. Only ASCII punctuation can be escaped, otherwise the backslash is
+/// just a backslash.
+fn parse_escape(input: &str) -> (&str, Vec<Token>) {
+    let escaped = &input[1..];
+    match escaped.chars().next() {
+        Some(c) if c.is_ascii_punctuation() => (
+            &escaped[1..],
+            vec![Token::Literal(escape_html(&c.to_string()))],
+        ),
+        _ => (escaped, vec![Token::Literal("\\".to_string())]),
+    }
+}
+
+/// Parse an entity like `&amp;`, `&#123;` or `&#x1F600;`, which is kept as it is.
+fn parse_entity(input: &str) -> IResult<&str, &str> {
+    recognize(delimited(
+        char('&'),
+        alt((
+            preceded(
+                tag("#"),
+                alt((
+                    preceded(one_of("xX"), take_while1(|c: char| c.is_ascii_hexdigit())),
+                    digit1,
+                )),
+            ),
+            take_while1(|c: char| c.is_ascii_alphanumeric()),
+        )),
+        char(';'),
+    ))(input)
+}
+
+/// Parse an inline HTML tag or comment, like `<kbd>` or `<!-- todo -->`, which gets an `html`
+/// block of its own so that it's passed through as is.
+fn parse_inline_html(input: &str) -> IResult<&str, Token> {
+    let (input, html) = alt((
+        recognize(delimited(tag("<!--"), take_until("-->"), tag("-->"))),
+        recognize(delimited(
+            preceded(char('<'), opt(char('/'))),
+            preceded(
+                take_while_m_n(1, 1, |c: char| c.is_ascii_alphabetic()),
+                take_while(|c| c != '<' && c != '>'),
+            ),
+            char('>'),
+        )),
+    ))(input)?;
+    Ok((
+        input,
+        Token::Block(Block {
+            kind: "html".to_string(),
+            tokens: vec![Token::Literal(html.to_string())],
+            meta: None,
+        }),
+    ))
+}
+
+/// Parse a character of plain text, escaping it as needed.
+fn parse_text_char(input: &str) -> (&str, Vec<Token>) {
+    if let Ok((rest, entity)) = parse_entity(input) {
+        return (rest, vec![Token::Literal(entity.to_string())]);
+    }
+    if let Ok((rest, html)) = parse_inline_html(input) {
+        return (rest, vec![html]);
+    }
+    let len = input.chars().next().map_or(0, char::len_utf8);
+    (
+        &input[len..],
+        vec![Token::Literal(escape_html(&input[..len]))],
+    )
+}
+
+/// Parse inline math, like `$x^2$`, or `$$x^2$$` for display math in the middle of a paragraph.
+/// Like with pandoc, the opening `$` can't be followed by whitespace, nor the closing one preceded
+/// by whitespace or followed by a digit, so that prices don't get mistaken for math.
+pub(crate) fn parse_math_span(input: &str) -> (&str, Vec<Token>) {
+    if let Some(rest) = input.strip_prefix("$$") {
+        return match rest.find("$$") {
+            Some(end) if !rest[..end].trim().is_empty() => (
+                &rest[end + 2..],
+                vec![Token::Block(make_math_block(&rest[..end], true))],
+            ),
+            _ => (rest, vec![Token::Literal("$$".to_string())]),
+        };
+    }
+    let rest = &input[1..];
+    if rest.starts_with(char::is_whitespace) {
+        return (rest, vec![Token::Literal("$".to_string())]);
+    }
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        if c == '$' && !escaped && i > 0 {
+            let before = rest[..i].chars().next_back();
+            let after = rest[i + 1..].chars().next();
+            // Nor can it be half of a `$$`.
+            if !before.is_some_and(|c| c.is_whitespace() || c == '$')
+                && !after.is_some_and(|c| c.is_ascii_digit() || c == '$')
+            {
+                let block = make_math_block(&rest[..i], false);
+                return (&rest[i + 1..], vec![Token::Block(block)]);
+            }
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    (rest, vec![Token::Literal("$".to_string())])
+}
+
+/// Parse a code span, which is closed by a backtick run of the same length as the one opening it.
+/// Its contents are taken verbatim, only escaped as HTML.
+fn parse_code_span(input: &str) -> (&str, Vec<Token>) {
+    let ticks = input.len() - input.trim_start_matches('`').len();
+    let (run, rest) = input.split_at(ticks);
+    let mut offset = 0;
+    while let Some(found) = rest[offset..].find(run) {
+        let start = offset + found;
+        let len = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        if len == ticks {
+            let code = rest[..start].replace('\n', " ");
+            // A single surrounding space is stripped, so that code can start or end with a tick.
+            let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                Some(stripped) if !stripped.trim().is_empty() => stripped.to_string(),
+                _ => code,
+            };
+            let block = Block {
+                kind: "code".to_string(),
+                tokens: vec![Token::Literal(escape_html(&code))],
+                meta: None,
+            };
+            return (&rest[start + ticks..], vec![Token::Block(block)]);
+        }
+        offset = start + len;
+    }
+    (rest, vec![Token::Literal(run.to_string())])
+}
+
+/// Determine whether a run of `delimiter`s, surrounded by `prev` and `next`, can open and/or close
+/// emphasis, per the CommonMark flanking rules.
+fn delimiter_flanking(delimiter: char, prev: Option<char>, next: Option<char>) -> (bool, bool) {
+    let is_space = |c: Option<char>| c.filter(|c| !c.is_whitespace()).is_none();
+    let is_punct = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation());
+    let left = !is_space(next) && (!is_punct(next) || is_space(prev) || is_punct(prev));
+    let right = !is_space(prev) && (!is_punct(prev) || is_space(next) || is_punct(next));
+    match delimiter {
+        // Underscores additionally can't be used for intraword emphasis, so `snake_case` is safe.
+        '_' => (
+            left && (!right || is_punct(prev)),
+            right && (!left || is_punct(next)),
+        ),
+        _ => (left, right),
+    }
+}
+
+/// How deeply emphasis may be nested, past which its delimiters are left as they were written.
+/// Blocks get rendered & cached recursively, so there has to be a limit somewhere.
+const MAX_EMPHASIS_DEPTH: usize = 16;
+
+/// A run of `*` or `_`, which may open and/or close emphasis once matched up with others.
+struct DelimiterRun {
+    delimiter: char,
+    // The length of the run as written, and how many of its delimiters are still unmatched.
+    length: usize,
+    remaining: usize,
+    can_open: bool,
+    can_close: bool,
+    // The kinds of emphasis closed & opened by the run, innermost first.
+    closes: Vec<&'static str>,
+    opens: Vec<&'static str>,
+}
+
+/// Match up the delimiter `runs` that open emphasis with those that close it, per the
+/// CommonMark algorithm: a single pass over the closers, looking back through a stack of
+/// potential openers. A single delimiter makes for `i` and a double for `b`, so a triple one
+/// ends up as both.
+fn match_emphasis(runs: &mut [DelimiterRun]) {
+    let mut openers: Vec<usize> = vec![];
+    // For each kind of closer, the depth of the stack below which no opener can match it, so
+    // that unmatched delimiters don't get looked through over and over again.
+    let mut bottoms: HashMap<(char, bool, usize), usize> = HashMap::new();
+    for closer in 0..runs.len() {
+        while runs[closer].can_close && runs[closer].remaining > 0 {
+            let DelimiterRun {
+                delimiter,
+                length,
+                can_open,
+                ..
+            } = runs[closer];
+            let key = (delimiter, can_open, length % 3);
+            let bottom = bottoms.get(&key).copied().unwrap_or(0);
+            let found = (bottom..openers.len()).rev().find(|&position| {
+                let opener = &runs[openers[position]];
+                // A run that could go either way can't pair up with one that makes for a
+                // multiple of 3 between them, unless both are, so that `*a**b*` stays one `i`.
+                let both_ways = opener.can_close || can_open;
+                let (opener_rest, rest) = (opener.length % 3, length % 3);
+                opener.delimiter == delimiter
+                    && !(both_ways
+                        && (opener_rest + rest) % 3 == 0
+                        && (opener_rest, rest) != (0, 0))
+            });
+            let Some(position) = found else {
+                bottoms.insert(key, openers.len());
+                break;
+            };
+            let opener = openers[position];
+            let (count, kind) = if runs[opener].remaining >= 2 && runs[closer].remaining >= 2 {
+                (2, "b")
+            } else {
+                (1, "i")
+            };
+            runs[opener].remaining -= count;
+            runs[opener].opens.push(kind);
+            runs[closer].remaining -= count;
+            runs[closer].closes.push(kind);
+            // Openers in between are left unmatched for good, as is a used up opener.
+            openers.truncate(position + 1);
+            if runs[opener].remaining == 0 {
+                openers.pop();
+            }
+            for bottom in bottoms.values_mut() {
+                *bottom = (*bottom).min(openers.len());
+            }
+        }
+        if runs[closer].can_open && runs[closer].remaining > 0 {
+            openers.push(closer);
+        }
+    }
+}
+
+/// Parse the inline markup (emphasis, code, links etc.) of a chunk of text.
+/// Emphasis is matched up only once everything else has been parsed, and the tokens are nested
+/// without any recursion, so that no amount of delimiters can overflow the stack.
+/// Any emphasis beyond `MAX_EMPHASIS_DEPTH` is left as literals.
 fn parse_inner(content: &str) -> Vec<Token> {
-    let mut content = content;
-    let mut result = vec![];
-    let mut literal = String::new();
-    while !content.is_empty() {
-        let (rest, nested) = opt(alt((
-            parse_nested_special_token('`', "code"),
-            parse_nested_special_token('*', "b"),
-            parse_nested_special_token('_', "i"),
-            parse_link_tag,
-        )))(content)
-        .unwrap();
-        content = if let Some(nested) = nested {
-            if !literal.is_empty() {
-                result.push(Token::Literal(literal));
-                literal = String::new();
+    // The tokens before each of the delimiter runs, and then after the last one.
+    let mut segments: Vec<Vec<Token>> = vec![vec![]];
+    let mut runs = vec![];
+    let mut rest = content;
+    while let Some(c) = rest.chars().next() {
+        let prev = content[..content.len() - rest.len()].chars().next_back();
+        let (next, parsed) = match c {
+            '\\' => parse_escape(rest),
+            '`' => parse_code_span(rest),
+            '$' => parse_math_span(rest),
+            '*' | '_' => {
+                let length = rest.len() - rest.trim_start_matches(c).len();
+                let (can_open, can_close) =
+                    delimiter_flanking(c, prev, rest[length..].chars().next());
+                runs.push(DelimiterRun {
+                    delimiter: c,
+                    length,
+                    remaining: length,
+                    can_open,
+                    can_close,
+                    closes: vec![],
+                    opens: vec![],
+                });
+                segments.push(vec![]);
+                rest = &rest[length..];
+                continue;
             }
-            result.push(nested);
-            rest
-        } else {
-            let (rest, next) = take::<usize, &str, nom::error::Error<&str>>(1usize)(rest).unwrap();
-            literal.push_str(next);
-            rest
+            _ => match alt((
+                parse_shortcode,
+                parse_image_tag,
+                parse_wikilink,
+                parse_footnote_ref,
+                parse_link_tag,
+            ))(rest)
+            {
+                Ok((next, token)) => (next, vec![token]),
+                Err(_) => parse_text_char(rest),
+            },
+        };
+        for token in parsed {
+            push_token(segments.last_mut().unwrap(), token);
+        }
+        rest = next;
+    }
+    match_emphasis(&mut runs);
+
+    // The emphasis currently open, innermost last, above the top level.
+    let mut stack: Vec<(&str, Vec<Token>)> = vec![("", vec![])];
+    // How many levels of emphasis within the innermost one on the `stack` were too deep.
+    let mut too_deep = 0;
+    let mut segments = segments.into_iter();
+    for run in runs {
+        for token in segments.next().unwrap() {
+            push_token(&mut stack.last_mut().unwrap().1, token);
+        }
+        let delimiters = |kind: &str| {
+            let count = if kind == "b" { 2 } else { 1 };
+            Token::Literal(run.delimiter.to_string().repeat(count))
         };
+        for kind in &run.closes {
+            if too_deep > 0 {
+                too_deep -= 1;
+                push_token(&mut stack.last_mut().unwrap().1, delimiters(kind));
+                continue;
+            }
+            let (kind, tokens) = stack.pop().unwrap();
+            let emphasis = Token::Block(Block {
+                kind: kind.to_string(),
+                tokens,
+                meta: None,
+            });
+            push_token(&mut stack.last_mut().unwrap().1, emphasis);
+        }
+        // Any unmatched delimiters are just literals.
+        if run.remaining > 0 {
+            let literal = run.delimiter.to_string().repeat(run.remaining);
+            push_token(&mut stack.last_mut().unwrap().1, Token::Literal(literal));
+        }
+        for kind in run.opens.iter().rev() {
+            if stack.len() > MAX_EMPHASIS_DEPTH {
+                too_deep += 1;
+                push_token(&mut stack.last_mut().unwrap().1, delimiters(kind));
+            } else {
+                stack.push((kind, vec![]));
+            }
+        }
+    }
+    for token in segments.flatten() {
+        push_token(&mut stack.last_mut().unwrap().1, token);
+    }
+    stack.pop().unwrap().1
+}
+
+/// Point relative image `src`s at the directory of the page that references them.
+pub fn resolve_image_sources(blocks: &mut [Block], rel_dir: &Utf8Path) {
+    for block in blocks {
+        if block.kind == "img"
+            && let Some(src) = block.meta.as_mut().and_then(|meta| meta.get_mut("src"))
+            && is_relative_url(src)
+        {
+            *src = resolve_relative_url(rel_dir, src);
+        }
+        for token in block.tokens.iter_mut() {
+            if let Token::Block(nested) = token {
+                resolve_image_sources(std::slice::from_mut(nested), rel_dir);
+            }
+        }
     }
-    if !literal.is_empty() {
-        result.push(Token::Literal(literal));
+}
+
+fn is_relative_url(url: &str) -> bool {
+    !(url.is_empty()
+        || url.starts_with('/')
+        || url.starts_with('#')
+        || url.starts_with("data:")
+        || url.contains("://"))
+}
+
+/// Join a relative `url` onto `rel_dir`, producing an absolute url path.
+fn resolve_relative_url(rel_dir: &Utf8Path, url: &str) -> String {
+    let mut segments: Vec<&str> = rel_dir.components().map(|c| c.as_str()).collect();
+    for segment in url.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
     }
-    result
+    format!("/{}", segments.join("/"))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use chrono::{TimeZone, Utc};
 
     use super::*;
+    use crate::common::{RenderRules, Taxonomy, TimestampSource, DEFAULT_RENDER_RULE_SET};
+
+    fn parse(contents: &str) -> MarkdownResult<Markdown> {
+        let (frontmatter, offset) = parse_frontmatter(contents)?;
+        Ok(parse_body(frontmatter, &contents[offset..]))
+    }
 
     #[test]
     fn test_parse_markdown() {
@@ -203,18 +1731,818 @@ Then I'd like to add another paragraph.
     }
 
     #[test]
-    fn test_parse_nested_special_token() {
-        let input = "`code is here`";
-        let parser = parse_nested_special_token('`', "code");
-        let (_, token) = opt(parser)(input).unwrap();
+    fn test_frontmatter_errors() {
+        let input = "---\ntitle: Bad Times\ntimestamp: 2023-13-21T10:00:00-05:00\n---\nBody.";
+        let e = parse(input)
+            .unwrap_err()
+            .with_path(Utf8Path::new("pages/blog/foo.md"));
+        let location = e.location().unwrap();
+        assert_eq!((location.line, location.column), (3, 12));
+        assert_eq!(location.snippet, "timestamp: 2023-13-21T10:00:00-05:00");
+        assert!(matches!(&e, MarkdownError::InvalidField { field, .. } if field == "timestamp"));
+        assert!(e
+            .to_string()
+            .starts_with("pages/blog/foo.md:3:12: invalid timestamp"));
+
+        let input = "\n---\ntimestamp: 2023-10-21T10:00:00-05:00\n---\nBody.";
+        let e = parse(input).unwrap_err();
+        assert!(matches!(&e, MarkdownError::InvalidField { field, .. } if field == "title"));
+        assert_eq!(e.location().unwrap().line, 2);
+        assert_eq!(e.to_string(), "2:1: missing `title`");
+
+        let input = "---\ntitle: [unclosed\n---\nBody.";
+        let e = parse(input).unwrap_err();
+        assert!(matches!(e, MarkdownError::InvalidFrontMatter { .. }));
+
+        let e = parse("No frontmatter here.").unwrap_err();
+        assert!(matches!(e, MarkdownError::ParseError { .. }));
+    }
+
+    #[test]
+    fn test_frontmatter_extra() {
+        let input = "---\ntitle: 1984\ntimestamp: 2023-10-21T10:00:00-05:00\ndescription: A novel.\n\
+                     featured: false\nauthors: [Orwell, Blair]\ncover:\n  src: cover.png\n  2: two\n---\nBody.";
+        let (frontmatter, _) = parse_frontmatter(input).unwrap();
+        assert_eq!(frontmatter.title, "1984");
+        assert_eq!(
+            frontmatter.extra.keys().collect::<Vec<_>>(),
+            vec!["authors", "cover", "description", "featured"],
+        );
+        assert_eq!(frontmatter.extra["description"], "A novel.");
+        assert_eq!(frontmatter.extra["featured"], false);
+        assert_eq!(frontmatter.extra["authors"][1], "Blair");
+        assert_eq!(frontmatter.extra["cover"]["2"], "two");
+
+        let input = "---\ntitle: [not, text]\ntimestamp: 2023-10-21T10:00:00-05:00\n---\nBody.";
+        let e = parse(input).unwrap_err();
+        assert_eq!(e.to_string(), "2:8: invalid title: expected a string");
+    }
+
+    #[test]
+    fn test_frontmatter_without_timestamp() {
+        let (frontmatter, _) = parse_frontmatter("---\ntitle: Undated\n---\nBody.").unwrap();
+        assert_eq!(frontmatter.timestamp_source, TimestampSource::Missing);
+        let (frontmatter, _) =
+            parse_frontmatter("---\ntitle: Dated\ntimestamp: 2023-10-21T10:00:00-05:00\n---\n")
+                .unwrap();
+        assert_eq!(frontmatter.timestamp_source, TimestampSource::Frontmatter);
+    }
+
+    #[test]
+    fn test_frontmatter_timezones() {
+        let input = "---\ntitle: Revised\ntimestamp: 2023-10-21T10:00:00-05:00\n\
+                     updated: 2023-11-02T09:30:00+09:00\n---\nBody.";
+        let (frontmatter, _) = parse_frontmatter(input).unwrap();
         assert_eq!(
-            token,
-            Some(Token::Block(Block {
-                kind: "code".to_string(),
-                tokens: vec![Token::Literal("code is here".to_string())],
+            frontmatter.timestamp.to_rfc3339(),
+            "2023-10-21T10:00:00-05:00"
+        );
+        let updated = frontmatter.updated.unwrap();
+        assert_eq!(updated.to_rfc3339(), "2023-11-02T09:30:00+09:00");
+        assert_eq!(
+            updated,
+            Utc.with_ymd_and_hms(2023, 11, 2, 0, 30, 0).unwrap()
+        );
+        assert!(frontmatter.extra.is_empty());
+
+        let (frontmatter, _) = parse_frontmatter("---\ntitle: Fresh\n---\nBody.").unwrap();
+        assert_eq!(frontmatter.updated, None);
+
+        let e = parse("---\ntitle: Post\nupdated: yesterday\n---\nBody.").unwrap_err();
+        assert!(e.to_string().starts_with("3:10: invalid updated"), "{}", e);
+    }
+
+    #[test]
+    fn test_frontmatter_publishing() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let published = |extra: &str| {
+            let input = format!("---\ntitle: Post\n{}---\nBody.", extra);
+            parse_frontmatter(&input).unwrap().0.is_published(now)
+        };
+        assert!(published(""));
+        assert!(published("draft: false\n"));
+        assert!(!published("draft: true\n"));
+        assert!(published("publish_at: 2023-12-31T00:00:00Z\n"));
+        assert!(!published("publish_at: 2024-01-02T00:00:00Z\n"));
+
+        let e = parse("---\ntitle: Post\ndraft: maybe\n---\nBody.").unwrap_err();
+        assert_eq!(e.to_string(), "3:8: invalid draft: expected true or false");
+    }
+
+    #[test]
+    fn test_frontmatter_terms() {
+        let input = "---\ntitle: Post\ntags: [Rust, Web Dev, 2024]\ncategories: Notes\n---\nBody.";
+        let (frontmatter, _) = parse_frontmatter(input).unwrap();
+        assert_eq!(
+            frontmatter.terms(Taxonomy::Tags),
+            ["Rust", "Web Dev", "2024"]
+        );
+        assert_eq!(frontmatter.terms(Taxonomy::Categories), ["Notes"]);
+        assert!(frontmatter.extra.is_empty());
+
+        let e = parse("---\ntitle: Post\ntags:\n  rust: true\n---\nBody.").unwrap_err();
+        assert_eq!(e.to_string(), "4:1: invalid tags: expected a list of terms");
+    }
+
+    #[test]
+    fn test_frontmatter_overrides() {
+        let rules = Arc::new(RenderRules {
+            block_rules: Some(HashMap::from([
+                ("p".to_string(), "plain".to_string()),
+                ("a".to_string(), "link".to_string()),
+            ])),
+            ..DEFAULT_RENDER_RULE_SET.as_ref().clone()
+        });
+        let (frontmatter, _) = parse_frontmatter("---\ntitle: Plain\n---\n").unwrap();
+        assert!(Arc::ptr_eq(&rules.for_page(&frontmatter), &rules));
+
+        let input = "---\ntitle: Landing\nlayouts: [primary, landing]\nblocks:\n  p: fancy\n---\n";
+        let (frontmatter, _) = parse_frontmatter(input).unwrap();
+        let page_rules = rules.for_page(&frontmatter);
+        assert_eq!(page_rules.layouts, ["primary", "landing"]);
+        assert_eq!(
+            page_rules.block_rules,
+            Some(HashMap::from([
+                ("p".to_string(), "fancy".to_string()),
+                ("a".to_string(), "link".to_string()),
+            ])),
+        );
+
+        let e = parse("---\ntitle: Post\nlayouts: landing\n---\nBody.").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "3:10: invalid layouts: expected a list of layout names"
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_formats() {
+        let yaml =
+            "---\ntitle: Hello\ntimestamp: 2023-10-21T10:00:00-05:00\ntags: [a, b]\n---\nBody.";
+        let toml = "+++\ntitle = \"Hello\"\ntimestamp = 2023-10-21T10:00:00-05:00\ntags = [\"a\", \"b\"]\n+++\nBody.";
+        let json = "{\n  \"title\": \"Hello\",\n  \"timestamp\": \"2023-10-21T10:00:00-05:00\",\n  \"tags\": [\"a\", \"b\"]\n}\nBody.";
+        let expected = parse_frontmatter(yaml).unwrap().0;
+        for input in [toml, json] {
+            let (frontmatter, offset) = parse_frontmatter(input).unwrap();
+            assert_eq!(frontmatter, expected);
+            assert_eq!(&input[offset..], "Body.");
+        }
+
+        let e = parse("+++\ntitle = \"Hi\"\ntimestamp = \"2023-13-21\"\n+++\nBody.").unwrap_err();
+        assert!(
+            e.to_string().starts_with("3:13: invalid timestamp"),
+            "{}",
+            e
+        );
+        let e = parse("{ \"title\": \"Hi\",\n  \"timestamp\": 1 }\nBody.").unwrap_err();
+        assert!(
+            e.to_string().starts_with("2:16: invalid timestamp"),
+            "{}",
+            e
+        );
+
+        let e = parse("+++\ntitle = \n+++\nBody.").unwrap_err();
+        assert!(matches!(
+            e,
+            MarkdownError::InvalidFrontMatter {
+                format: FrontMatterFormat::Toml,
+                ..
+            }
+        ));
+        assert!(
+            e.to_string().starts_with("2:9: invalid TOML frontmatter:"),
+            "{}",
+            e
+        );
+        let e = parse("{\n  \"title\": \"Hi\"\n  \"timestamp\": 1\n}\nBody.").unwrap_err();
+        assert!(
+            e.to_string().starts_with("3:3: invalid JSON frontmatter:"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn test_parse_fenced_code() {
+        let input = "Some code:\n```rust\nfn main() {\n    let x = 1;\n\n    println!(\"{x}\");\n}\n```\n\n~~~\n\n  indented\n~~~";
+        let blocks = parse_blocks(input);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].kind, "p");
+        assert_eq!(
+            blocks[1],
+            Block {
+                kind: "pre".to_string(),
+                tokens: vec![Token::Literal(
+                    "fn main() {\n    let x = 1;\n\n    println!(\"{x}\");\n}".to_string()
+                )],
+                meta: Some(HashMap::from([(
+                    "language".to_string(),
+                    "rust".to_string()
+                )])),
+            },
+        );
+        assert_eq!(
+            blocks[2],
+            Block {
+                kind: "pre".to_string(),
+                tokens: vec![Token::Literal("\n  indented".to_string())],
+                meta: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        let input =
+            "Shopping:\n- eggs\n- milk\n  1. whole\n  2. _skim_\n* bread\n\n3. three\n4. four";
+        let blocks = parse_blocks(input);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].kind, "p");
+
+        let li = |tokens| {
+            Token::Block(Block {
+                kind: "li".to_string(),
+                tokens,
+                meta: None,
+            })
+        };
+        let literal = |s: &str| Token::Literal(s.to_string());
+        assert_eq!(
+            blocks[1],
+            Block {
+                kind: "ul".to_string(),
+                tokens: vec![
+                    li(vec![literal("eggs")]),
+                    li(vec![
+                        literal("milk"),
+                        Token::Block(Block {
+                            kind: "ol".to_string(),
+                            tokens: vec![
+                                li(vec![literal("whole")]),
+                                li(vec![Token::Block(Block {
+                                    kind: "i".to_string(),
+                                    tokens: vec![literal("skim")],
+                                    meta: None,
+                                })]),
+                            ],
+                            meta: None,
+                        }),
+                    ]),
+                    li(vec![literal("bread")]),
+                ],
+                meta: None,
+            },
+        );
+        assert_eq!(blocks[2].kind, "ol");
+        assert_eq!(blocks[2].tokens.len(), 2);
+        assert_eq!(
+            blocks[2].meta,
+            Some(HashMap::from([("start".to_string(), "3".to_string())])),
+        );
+    }
+
+    #[test]
+    fn test_parse_list_item_blocks() {
+        let block = |kind: &str, tokens| {
+            Token::Block(Block {
+                kind: kind.to_string(),
+                tokens,
+                meta: None,
+            })
+        };
+        let literal = |s: &str| Token::Literal(s.to_string());
+
+        // Code inside of an item doesn't cut the list short.
+        let blocks = parse_blocks("- item\n  ```rust\n  let x = 1;\n  ```\n- next");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].tokens,
+            vec![
+                block(
+                    "li",
+                    vec![
+                        literal("item"),
+                        Token::Block(Block {
+                            kind: "pre".to_string(),
+                            tokens: vec![literal("let x = 1;")],
+                            meta: Some(HashMap::from([(
+                                "language".to_string(),
+                                "rust".to_string()
+                            )])),
+                        }),
+                    ]
+                ),
+                block("li", vec![literal("next")]),
+            ],
+        );
+
+        // Paragraphs of loose items keep apart.
+        let blocks = parse_blocks("- first,\n\n  second\n- next");
+        assert_eq!(
+            blocks[0].tokens,
+            vec![
+                block(
+                    "li",
+                    vec![
+                        block("p", vec![literal("first,")]),
+                        block("p", vec![literal("second")]),
+                    ]
+                ),
+                block("li", vec![block("p", vec![literal("next")])]),
+            ],
+        );
+        // Only the nested list is loose here.
+        let blocks = parse_blocks("- a\n  - b\n\n  - c");
+        assert_eq!(
+            blocks[0].tokens,
+            vec![block(
+                "li",
+                vec![
+                    literal("a"),
+                    block(
+                        "ul",
+                        vec![
+                            block("li", vec![block("p", vec![literal("b")])]),
+                            block("li", vec![block("p", vec![literal("c")])]),
+                        ]
+                    ),
+                ]
+            )],
+        );
+
+        // Thematic breaks aren't list items.
+        let blocks = parse_blocks("* * *\n- a\n* * *\n- b\n\n---");
+        assert_eq!(
+            blocks.iter().map(|b| b.kind.as_str()).collect::<Vec<_>>(),
+            vec!["hr", "ul", "hr", "ul", "hr"],
+        );
+    }
+
+    #[test]
+    fn test_parse_blockquote() {
+        let input =
+            "> ## Quoted\n>\n> First paragraph,\nlazily continued.\n>\n> - a list\n\nAfter.";
+        let blocks = parse_blocks(input);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].kind, "blockquote");
+        assert_eq!(blocks[1].kind, "p");
+
+        let children: Vec<&Block> = blocks[0]
+            .tokens
+            .iter()
+            .map(|token| match token {
+                Token::Block(block) => block,
+                _ => panic!("expected only blocks in a blockquote"),
+            })
+            .collect();
+        assert_eq!(
+            children.iter().map(|b| b.kind.as_str()).collect::<Vec<_>>(),
+            vec!["h2", "p", "ul"],
+        );
+        assert_eq!(
+            children[1].tokens,
+            vec![Token::Literal(
+                "First paragraph,\nlazily continued.".to_string()
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_image_tag() {
+        let tokens = parse_inner(r#"See ![a cat](cat.png "Mr. Whiskers") and ![](/abs.png)"#);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Literal("See ".to_string()),
+                Token::Block(Block {
+                    kind: "img".to_string(),
+                    tokens: vec![],
+                    meta: Some(HashMap::from([
+                        ("src".to_string(), "cat.png".to_string()),
+                        ("alt".to_string(), "a cat".to_string()),
+                        ("title".to_string(), "Mr. Whiskers".to_string()),
+                    ])),
+                }),
+                Token::Literal(" and ".to_string()),
+                Token::Block(Block {
+                    kind: "img".to_string(),
+                    tokens: vec![],
+                    meta: Some(HashMap::from([
+                        ("src".to_string(), "/abs.png".to_string()),
+                        ("alt".to_string(), "".to_string()),
+                    ])),
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_resolve_image_sources() {
+        let mut blocks =
+            parse_blocks("![](cat.png) ![](../dog.png) ![](https://example.com/x.png)");
+        resolve_image_sources(&mut blocks, Utf8Path::new("blog/pets"));
+        let srcs: Vec<&str> = blocks[0]
+            .tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Block(block) => Some(block.meta.as_ref().unwrap()["src"].as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            srcs,
+            vec![
+                "/blog/pets/cat.png",
+                "/blog/dog.png",
+                "https://example.com/x.png"
+            ],
+        );
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let input = "| Name | Qty | Note |\n|:-----|----:|:----:|\n| `a\\|b` | 1 |\n| c | 2 | _x_ | extra |\n\nAfter.";
+        let blocks = parse_blocks(input);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].kind, "table");
+
+        let children = |token: &Token| match token {
+            Token::Block(block) => block.tokens.clone(),
+            _ => panic!("expected a block"),
+        };
+        let (thead, tbody) = (&blocks[0].tokens[0], &blocks[0].tokens[1]);
+        let header = children(&children(thead)[0]);
+        let rows = children(tbody);
+        assert_eq!(header.len(), 3);
+        assert_eq!(rows.len(), 2);
+
+        let align = |cell: &Token| match cell {
+            Token::Block(block) => block.meta.as_ref().map(|meta| meta["align"].clone()),
+            _ => None,
+        };
+        assert_eq!(
+            header.iter().map(align).collect::<Vec<_>>(),
+            vec![
+                Some("left".to_string()),
+                Some("right".to_string()),
+                Some("center".to_string())
+            ],
+        );
+
+        // Short rows get padded out, long ones truncated.
+        let first_row = children(&rows[0]);
+        assert_eq!(first_row.len(), 3);
+        assert_eq!(
+            children(&children(&first_row[0])[0]),
+            vec![Token::Literal("a|b".to_string())],
+        );
+        assert_eq!(children(&first_row[2]), vec![]);
+        assert_eq!(children(&rows[1]).len(), 3);
+    }
+
+    #[test]
+    fn test_collect_summary() {
+        let frontmatter = "---\ntitle: Summaries\ntimestamp: 2023-10-21T10:00:00-05:00\n";
+        let kinds = |blocks: &[Block]| blocks.iter().map(|b| b.kind.clone()).collect::<Vec<_>>();
+
+        let markdown = parse(&format!(
+            "{}---\n# Intro\n\n> Quote\n\nFirst.\n<!-- more -->\nRest.",
+            frontmatter
+        ))
+        .unwrap();
+        assert_eq!(kinds(&markdown.summary), vec!["h1", "blockquote", "p"]);
+        assert_eq!(kinds(&markdown.blocks), vec!["h1", "blockquote", "p", "p"]);
+
+        // A marker within another block cuts it short in the summary.
+        let markdown = parse(&format!(
+            "{}---\n# Intro\n\n> Quote\n>\n> <!-- more -->\n>\n> Rest.\n\nMore.",
+            frontmatter
+        ))
+        .unwrap();
+        assert_eq!(kinds(&markdown.summary), vec!["h1", "blockquote"]);
+        assert_eq!(markdown.summary[1].tokens.len(), 1);
+        assert_eq!(kinds(&markdown.blocks), vec!["h1", "blockquote", "p"]);
+        assert_eq!(markdown.blocks[1].tokens.len(), 2);
+
+        // Without a marker, it's just the first paragraph.
+        let markdown = parse(&format!("{}---\n# Intro\n\nFirst.\n\nSecond.", frontmatter)).unwrap();
+        assert_eq!(
+            markdown.summary,
+            vec![Block {
+                kind: "p".to_string(),
+                tokens: vec![Token::Literal("First.".to_string())],
+                meta: None,
+            }],
+        );
+
+        // The frontmatter takes precedence over the marker.
+        let markdown = parse(&format!(
+            "{}summary: A *custom* summary.\n---\nFirst.\n\n<!--more-->\n\nRest.",
+            frontmatter
+        ))
+        .unwrap();
+        assert_eq!(kinds(&markdown.summary), vec!["p"]);
+        assert_eq!(markdown.summary[0].tokens.len(), 3);
+        assert_eq!(kinds(&markdown.blocks), vec!["p", "p"]);
+
+        // Footnotes are numbered in the summary, and their definitions kept out of it.
+        let markdown = parse(&format!(
+            "{}---
+A claim.[^1]
+
+[^1]: The source.
+
+<!-- more -->
+
+Rest.",
+            frontmatter
+        ))
+        .unwrap();
+        assert_eq!(kinds(&markdown.summary), vec!["p"]);
+        assert_eq!(kinds(&markdown.blocks), vec!["p", "p", "footnotes"]);
+        let Token::Block(footnote_ref) = &markdown.summary[0].tokens[1] else {
+            panic!("expected a footnote reference");
+        };
+        let meta = footnote_ref.meta.as_ref().unwrap();
+        assert_eq!(meta["number"], "1");
+        assert_eq!(meta["href"], "#fn-1");
+    }
+
+    #[test]
+    fn test_parse_footnotes() {
+        let input = r#"
+---
+title: Footnotes
+timestamp: 2023-10-21T10:00:00-05:00
+---
+First[^b], second[^a] and again[^b], but not[^missing].
+
+[^a]: The _second_ note.
+
+[^b]: The first note.
+
+[^unused]: Never referenced.
+        "#;
+        let markdown = parse(input).unwrap();
+
+        assert_eq!(markdown.footnotes.len(), 2);
+        let labels: Vec<(&str, &str)> = markdown
+            .footnotes
+            .iter()
+            .map(|footnote| {
+                let meta = footnote.meta.as_ref().unwrap();
+                (meta["label"].as_str(), meta["number"].as_str())
+            })
+            .collect();
+        assert_eq!(labels, vec![("b", "1"), ("a", "2")]);
+
+        assert_eq!(
+            markdown
+                .blocks
+                .iter()
+                .map(|b| b.kind.as_str())
+                .collect::<Vec<_>>(),
+            vec!["p", "footnotes"],
+        );
+        let refs: Vec<&HashMap<String, String>> = markdown.blocks[0]
+            .tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Block(block) => block.meta.as_ref(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(refs.len(), 3);
+        assert_eq!(refs[0]["id"], "fnref-b");
+        assert_eq!(refs[0]["href"], "#fn-b");
+        assert_eq!(refs[1]["number"], "2");
+        assert_eq!(refs[2]["id"], "fnref-b-2");
+        assert!(markdown.blocks[0]
+            .tokens
+            .contains(&Token::Literal("[^missing]".to_string())));
+    }
+
+    /// Render `tokens` compactly, for ease of comparison.
+    fn render_inline(tokens: &[Token]) -> String {
+        tokens
+            .iter()
+            .map(|token| match token {
+                Token::Literal(literal) => literal.clone(),
+                Token::Block(block) => format!(
+                    "<{}>{}</{}>",
+                    block.kind,
+                    render_inline(&block.tokens),
+                    block.kind
+                ),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_inner_corpus() {
+        let corpus = [
+            ("plain text", "plain text"),
+            ("*em* and **strong**", "<i>em</i> and <b>strong</b>"),
+            ("_em_ and __strong__", "<i>em</i> and <b>strong</b>"),
+            ("***both***", "<i><b>both</b></i>"),
+            ("*a **b** c*", "<i>a <b>b</b> c</i>"),
+            ("**a *b* c**", "<b>a <i>b</i> c</b>"),
+            // Intraword.
+            ("snake_case_name", "snake_case_name"),
+            ("__init__.py", "<b>init</b>.py"),
+            ("foo*bar*baz", "foo<i>bar</i>baz"),
+            ("_(parenthetical)_", "<i>(parenthetical)</i>"),
+            // Delimiters that can't open or close.
+            ("2 * 3 * 4", "2 * 3 * 4"),
+            ("a * b", "a * b"),
+            ("*foo bar *", "*foo bar *"),
+            ("** not strong **", "** not strong **"),
+            // Unmatched delimiters don't swallow the rest of the text.
+            ("an *unclosed delimiter", "an *unclosed delimiter"),
+            ("an _unclosed delimiter", "an _unclosed delimiter"),
+            ("**foo*", "*<i>foo</i>"),
+            ("*foo**", "<i>foo</i>*"),
+            ("*a**b*", "<i>a**b</i>"),
+            ("****a****", "<b><b>a</b></b>"),
+            ("_a *b_ c*", "<i>a *b</i> c*"),
+            // Escapes.
+            (r"\*not em\*", "*not em*"),
+            (r"\_\`\\", r"_`\"),
+            (r"a \q b", r"a \q b"),
+            (r"trailing \", r"trailing \"),
+            // Code spans.
+            ("`code is here`", "<code>code is here</code>"),
+            ("`*not em*`", "<code>*not em*</code>"),
+            ("`` a ` tick ``", "<code>a ` tick</code>"),
+            ("`unclosed code", "`unclosed code"),
+            ("*em `with * code`*", "<i>em <code>with * code</code></i>"),
+            // Links.
+            ("_see [here](/a_b/)_", "<i>see <a>here</a></i>"),
+        ];
+        for (input, expected) in corpus {
+            assert_eq!(render_inline(&parse_inner(input)), expected, "{:?}", input);
+        }
+
+        // Neither deep nesting nor heaps of unmatched delimiters should blow up.
+        let nested = format!("{}{}", "**a ".repeat(800), "b** ".repeat(800));
+        assert_eq!(
+            render_inline(&parse_inner(&nested)),
+            format!(
+                "{}{}{}{}",
+                "<b>a ".repeat(MAX_EMPHASIS_DEPTH),
+                "**a ".repeat(800 - MAX_EMPHASIS_DEPTH),
+                "b** ".repeat(800 - MAX_EMPHASIS_DEPTH),
+                "b</b> ".repeat(MAX_EMPHASIS_DEPTH),
+            ),
+        );
+        let unmatched = "*a _b ".repeat(1600);
+        assert_eq!(render_inline(&parse_inner(&unmatched)), unmatched);
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let input = ":::tabs\n::: tab title=Rust\nFirst paragraph.\n\n```rust\n:::\n```\n:::\n\n:::tab title=\"Plain text\"\n- one\n- two\n:::\n:::\nAfter.\n\n:::details\nUnclosed.";
+        let blocks = parse_blocks(input);
+        let kinds = blocks.iter().map(|b| b.kind.as_str()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["tabs", "p", "details"]);
+
+        let tabs = blocks[0]
+            .tokens
+            .iter()
+            .map(|token| match token {
+                Token::Block(block) => block,
+                _ => panic!("expected a block"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].kind, "tab");
+        assert_eq!(tabs[0].meta.as_ref().unwrap()["title"], "Rust");
+        assert_eq!(tabs[1].meta.as_ref().unwrap()["title"], "Plain text");
+        // The fence inside the code block doesn't close the tab early.
+        let children = |block: &Block| {
+            block
+                .tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Block(block) => block.kind.clone(),
+                    _ => panic!("expected a block"),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(children(tabs[0]), vec!["p", "pre"]);
+        assert_eq!(children(tabs[1]), vec!["ul"]);
+        assert_eq!(children(&blocks[2]), vec!["p"]);
+
+        // A container opening cuts a paragraph short.
+        let blocks = parse_blocks("Text.\n:::note\nNoted.\n:::");
+        let kinds = blocks.iter().map(|b| b.kind.as_str()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["p", "note"]);
+    }
+
+    #[test]
+    fn test_parse_custom_kind() {
+        let block =
+            parse_block("~:callout type=warning title=\"Heads \\\"up\\\"\" open\nCareful now.");
+        assert_eq!(block.kind, "callout");
+        assert_eq!(
+            block.meta,
+            Some(HashMap::from([
+                ("type".to_string(), "warning".to_string()),
+                ("title".to_string(), "Heads \"up\"".to_string()),
+                ("open".to_string(), "true".to_string()),
+            ])),
+        );
+        assert_eq!(
+            block.tokens,
+            vec![Token::Literal("Careful now.".to_string())]
+        );
+
+        let block = parse_block("~:aside \nNo arguments.");
+        assert_eq!(block.kind, "aside");
+        assert_eq!(block.meta, None);
+
+        // An unterminated quote isn't a valid declaration, so it's left as a paragraph.
+        let block = parse_block("~:callout title=\"oops\nText.");
+        assert_eq!(block.kind, "p");
+    }
+
+    #[test]
+    fn test_parse_shortcodes() {
+        let shortcode = |meta: &[(&str, &str)]| {
+            Token::Block(Block {
+                kind: "shortcode".to_string(),
+                tokens: vec![],
+                meta: Some(
+                    meta.iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                ),
+            })
+        };
+        assert_eq!(
+            parse_inner("Press {{< button label=\"Buy now\" >}} to buy."),
+            vec![
+                Token::Literal("Press ".to_string()),
+                shortcode(&[("shortcode", "button"), ("label", "Buy now")]),
+                Token::Literal(" to buy.".to_string()),
+            ],
+        );
+        assert_eq!(
+            parse_inner("Not {{< quite }}"),
+            vec![Token::Literal("Not {{&lt; quite }}".to_string())],
+        );
+
+        // A shortcode on its own isn't wrapped in a paragraph.
+        let contents = "Intro.\n\n{{<youtube id=abc123>}}\n\n> Quote {{< missing >}}";
+        let blocks = parse_blocks(contents);
+        assert_eq!(
+            Token::Block(blocks[1].clone()),
+            shortcode(&[("shortcode", "youtube"), ("id", "abc123")]),
+        );
+
+        let exists = |name: &str| name == "youtube";
+        assert!(check_shortcodes(contents, &blocks[..2], &exists).is_ok());
+        let error = check_shortcodes(contents, &blocks, &exists).unwrap_err();
+        assert_eq!(error.to_string(), "5:9: unknown shortcode `missing`");
+    }
+
+    #[test]
+    fn test_parse_math() {
+        let math = |source: &str, display: &str| {
+            Token::Block(Block {
+                kind: "math".to_string(),
+                tokens: vec![Token::Literal(source.to_string())],
+                meta: Some(HashMap::from([(
+                    "display".to_string(),
+                    display.to_string(),
+                )])),
+            })
+        };
+        assert_eq!(
+            parse_inner("So $x^2 + \\$y$ costs $5 and $6, or $$\\int x$$."),
+            vec![
+                Token::Literal("So ".to_string()),
+                math("x^2 + \\$y", "inline"),
+                Token::Literal(" costs $5 and $6, or ".to_string()),
+                math("\\int x", "block"),
+                Token::Literal(".".to_string()),
+            ],
+        );
+        // Emphasis doesn't reach into math.
+        assert_eq!(
+            parse_inner("*a $b*c$*"),
+            vec![Token::Block(Block {
+                kind: "i".to_string(),
+                tokens: vec![Token::Literal("a ".to_string()), math("b*c", "inline")],
                 meta: None,
-            })),
+            })],
         );
+
+        let blocks = parse_blocks("$$\na + b\n$$\nAfter.\n\n$$ c $$ and more");
+        assert_eq!(Token::Block(blocks[0].clone()), math("a + b", "block"));
+        assert_eq!(blocks[1].kind, "p");
+        assert_eq!(blocks[2].kind, "p");
+        assert_eq!(blocks[2].tokens[0], math("c", "block"));
     }
 
     #[test]
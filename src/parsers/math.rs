@@ -0,0 +1,80 @@
+use crate::common::{Block, Token};
+use crate::parsers::markdown::{MarkdownError, SourceLocation};
+
+/// Convert the LaTeX of every `math` block to MathML, stored in its `meta` as `mathml`, so that
+/// pages don't need any client-side JavaScript to display it.
+/// The `contents` the `blocks` were parsed from are used to point out any invalid expression.
+pub fn render_math(contents: &str, blocks: &mut [Block]) -> Result<(), MarkdownError> {
+    for block in blocks {
+        if block.kind == "math" {
+            let source = match block.tokens.first() {
+                Some(Token::Literal(source)) => source.clone(),
+                _ => String::new(),
+            };
+            let meta = block.meta.get_or_insert_with(Default::default);
+            let display = meta.get("display").is_some_and(|d| d == "block");
+            let mathml =
+                to_mathml(&source, display).map_err(|message| MarkdownError::ParseError {
+                    location: SourceLocation::new(contents, contents.find(&source).unwrap_or(0)),
+                    message: format!("invalid math `{}`: {}", source, message),
+                })?;
+            meta.insert("mathml".to_string(), mathml);
+            continue;
+        }
+        for token in block.tokens.iter_mut() {
+            if let Token::Block(nested) = token {
+                render_math(contents, std::slice::from_mut(nested))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_mathml(source: &str, display: bool) -> Result<String, String> {
+    let opts = katex::Opts::builder()
+        .output_type(katex::OutputType::Mathml)
+        .display_mode(display)
+        .throw_on_error(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    katex::render_with_opts(source, &opts).map_err(|e| match e {
+        // KaTeX's own message comes wrapped up in the debug output of a JS value.
+        katex::Error::JsExecError(detail) => detail
+            .trim_start_matches("String(\"")
+            .trim_end_matches("\")")
+            .replace("\\\\", "\\")
+            .replace("\\\"", "\""),
+        e => e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::markdown::parse_blocks;
+
+    #[test]
+    fn test_render_math() {
+        let contents = "Energy: $E = mc^2$.\n\n$$\n\\sum_{i=1}^n i\n$$";
+        let mut blocks = parse_blocks(contents);
+        render_math(contents, &mut blocks).unwrap();
+        let Token::Block(inline) = &blocks[0].tokens[1] else {
+            panic!("expected inline math");
+        };
+        let mathml = &inline.meta.as_ref().unwrap()["mathml"];
+        assert!(mathml.contains("<math"));
+        assert!(!mathml.contains("display=\"block\""));
+        assert!(blocks[1].meta.as_ref().unwrap()["mathml"].contains("display=\"block\""));
+
+        let contents = "Broken:\n\nThis $\\frac{1$ here.";
+        let mut blocks = parse_blocks(contents);
+        let error = render_math(contents, &mut blocks).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("3:7: invalid math `\\frac{1`: ParseError: KaTeX parse error:"),
+            "{}",
+            error,
+        );
+    }
+}
@@ -1 +1,5 @@
+pub(crate) mod commonmark;
 pub(crate) mod markdown;
+pub(crate) mod math;
+pub(crate) mod typography;
+pub(crate) mod wikilinks;
@@ -0,0 +1,121 @@
+use crate::common::{Block, Token};
+
+/// Block kinds whose text must be left exactly as written.
+const VERBATIM_KINDS: [&str; 4] = ["code", "pre", "html", "math"];
+
+/// Apply typographic niceties to the literal text of the `blocks`: curly quotes, en & em dashes
+/// and ellipses. Code & inline HTML are left alone, as is anything in `meta`, like link hrefs.
+pub fn smarten(blocks: &mut [Block]) {
+    for block in blocks {
+        // Quotes may open in one token and close in another, so carry the context along.
+        let mut prev = None;
+        smarten_block(block, &mut prev);
+    }
+}
+
+fn smarten_block(block: &mut Block, prev: &mut Option<char>) {
+    if block.kind == "html" {
+        // Markup is invisible to the reader, so shouldn't sway the quotes around it either.
+        return;
+    }
+    if VERBATIM_KINDS.contains(&block.kind.as_str()) {
+        // Treat code like a word, so that quotes right after it close rather than open.
+        *prev = Some('x');
+        return;
+    }
+    for token in block.tokens.iter_mut() {
+        match token {
+            Token::Literal(literal) => *literal = smarten_text(literal, prev),
+            Token::Block(nested) => smarten_block(nested, prev),
+        }
+    }
+}
+
+/// Whether a quote following `prev` should open rather than close.
+fn opens_quote(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{\u{2013}\u{2014}".contains(c),
+    }
+}
+
+/// Smarten the `text`. Any raw HTML is in blocks of its own, so there's no markup to skip over.
+fn smarten_text(text: &str, prev: &mut Option<char>) -> String {
+    let text = text
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("...", "\u{2026}");
+    let mut smartened = String::with_capacity(text.len());
+    for c in text.chars() {
+        let c = match c {
+            '"' if opens_quote(*prev) => '\u{201C}',
+            '"' => '\u{201D}',
+            '\'' if opens_quote(*prev) => '\u{2018}',
+            // Apostrophes are the same character as closing single quotes.
+            '\'' => '\u{2019}',
+            c => c,
+        };
+        smartened.push(c);
+        *prev = Some(c);
+    }
+    smartened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::commonmark;
+    use crate::parsers::markdown::parse_blocks;
+
+    fn render(blocks: &[Block]) -> String {
+        fn render_tokens(tokens: &[Token]) -> String {
+            tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Literal(literal) => literal.clone(),
+                    Token::Block(block) => render_tokens(&block.tokens),
+                })
+                .collect()
+        }
+        blocks
+            .iter()
+            .map(|block| render_tokens(&block.tokens))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_smarten() {
+        let mut blocks = parse_blocks(
+            "\"Hello,\" she said -- it's the 'best'... isn't it---*\"really\"*?\n\n\
+             Run `\"quoted\" -- ...` and \"`code`\" or [see 'here'](/it's--fine...).\n\n\
+             ```\nlet s = \"x\" -- 1;\n```",
+        );
+        smarten(&mut blocks);
+        assert_eq!(
+            render(&blocks),
+            "\u{201C}Hello,\u{201D} she said \u{2013} it\u{2019}s the \u{2018}best\u{2019}\u{2026} \
+             isn\u{2019}t it\u{2014}\u{201C}really\u{201D}?\n\
+             Run \"quoted\" -- ... and \u{201C}code\u{201D} or see \u{2018}here\u{2019}.\n\
+             let s = \"x\" -- 1;",
+        );
+        // The href of the link is untouched.
+        let href = blocks[1].tokens.iter().find_map(|token| match token {
+            Token::Block(block) if block.kind == "a" => block.meta.as_ref()?.get("href"),
+            _ => None,
+        });
+        assert_eq!(href.map(String::as_str), Some("/it's--fine..."));
+    }
+
+    #[test]
+    fn test_smarten_skips_html() {
+        let input = "Said <a href=\"/x\" title='y'>\"hi\"</a> <!-- a -- note --> 1 < 2 -- ok";
+        let expected = "Said <a href=\"/x\" title='y'>\u{201C}hi\u{201D}</a> <!-- a -- note --> \
+                        1 &lt; 2 \u{2013} ok";
+        // Both parsers hand over inline HTML as blocks of its own, and escape the rest.
+        for mut blocks in [parse_blocks(input), commonmark::parse_blocks(input)] {
+            smarten(&mut blocks);
+            assert_eq!(render(&blocks), expected);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Block, Token};
+use crate::parsers::markdown::{MarkdownError, SourceLocation, WIKILINK_KIND};
+use crate::utils::escape_html;
+
+/// Every page of the site, by source path and by title, for resolving wiki-style links.
+#[derive(Debug, Default)]
+pub struct SiteIndex {
+    // Source path relative to the pages directory, without extension, to url path.
+    urls_by_path: HashMap<String, String>,
+    urls_by_title: HashMap<String, String>,
+    titles_by_url: HashMap<String, String>,
+}
+
+impl SiteIndex {
+    /// Add the page from `rel_path` (relative to the pages directory), which is served at
+    /// `url_path`.
+    pub fn insert(&mut self, rel_path: &Utf8Path, url_path: &str, title: Option<&str>) {
+        let path = rel_path.with_extension("").to_string();
+        self.urls_by_path.insert(path, url_path.to_string());
+        if let Some(title) = title {
+            // Should two pages share a title, the first one wins.
+            self.urls_by_title
+                .entry(title.to_string())
+                .or_insert_with(|| url_path.to_string());
+            self.titles_by_url
+                .insert(url_path.to_string(), title.to_string());
+        }
+    }
+
+    /// Find the url path of the `target`, trying it as a path relative to `rel_dir`, then as a path
+    /// relative to the pages directory, then as a title.
+    fn lookup(&self, target: &str, rel_dir: &Utf8Path) -> Option<&String> {
+        let path = target.trim_start_matches('/');
+        let path = path.strip_suffix(".md").unwrap_or(path);
+        let relative = (!target.starts_with('/')).then(|| rel_dir.join(path).to_string());
+        relative
+            .and_then(|relative| self.urls_by_path.get(&relative))
+            .or_else(|| self.urls_by_path.get(path))
+            .or_else(|| self.urls_by_title.get(target))
+    }
+
+    /// The text for a link to the `page` at `url` without any of its own: the title of the page,
+    /// if it has one.
+    fn link_text<'a>(&'a self, page: &'a str, url: &str) -> &'a str {
+        self.titles_by_url.get(url).map_or(page, String::as_str)
+    }
+
+    /// Whether the `link` from a page in `rel_dir` would still resolve the way it did.
+    pub fn resolves_same(&self, link: &ResolvedLink, rel_dir: &Utf8Path) -> bool {
+        let Some(url) = self.lookup(&link.page, rel_dir) else {
+            return false;
+        };
+        *url == link.url
+            && match &link.text {
+                Some(text) => text == self.link_text(&link.page, url),
+                None => true,
+            }
+    }
+}
+
+/// A wiki-style link as it was resolved, so that a cached page can tell whether it's gone stale.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedLink {
+    // The page linked to, as written.
+    pub page: String,
+    pub url: String,
+    // The text filled in for a link without any of its own.
+    pub text: Option<String>,
+}
+
+/// Turn the wiki-style links in the `blocks` of the page in `rel_dir` into plain `a` links,
+/// pointing out any that don't lead anywhere within the source `contents`.
+/// Links without any text of their own get the title of the page they link to.
+/// Each link gets recorded in `resolved` as well.
+pub fn resolve_wikilinks(
+    contents: &str,
+    blocks: &mut [Block],
+    index: &SiteIndex,
+    rel_dir: &Utf8Path,
+    resolved: &mut Vec<ResolvedLink>,
+) -> Result<(), MarkdownError> {
+    for block in blocks {
+        if block.kind == WIKILINK_KIND {
+            let meta = block.meta.get_or_insert_with(Default::default);
+            let target = meta.remove("target").unwrap_or_default();
+            let (page, fragment) = match target.split_once('#') {
+                Some((page, fragment)) => (page, Some(fragment)),
+                None => (target.as_str(), None),
+            };
+            let url = index.lookup(page, rel_dir).ok_or_else(|| {
+                let offset = contents.find(&format!("[[{}", target)).unwrap_or(0);
+                MarkdownError::ParseError {
+                    location: SourceLocation::new(contents, offset),
+                    message: format!("unresolved link to `{}`", target),
+                }
+            })?;
+            let href = match fragment {
+                Some(fragment) => format!("/{}#{}", url, fragment),
+                None => format!("/{}", url),
+            };
+            meta.insert("href".to_string(), href);
+            let text = block.tokens.is_empty().then(|| {
+                let text = index.link_text(page, url).to_string();
+                block.tokens.push(Token::Literal(escape_html(&text)));
+                text
+            });
+            resolved.push(ResolvedLink {
+                page: page.to_string(),
+                url: url.clone(),
+                text,
+            });
+            block.kind = "a".to_string();
+        }
+        for token in block.tokens.iter_mut() {
+            if let Token::Block(nested) = token {
+                let nested = std::slice::from_mut(nested);
+                resolve_wikilinks(contents, nested, index, rel_dir, resolved)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::markdown::parse_blocks;
+
+    #[test]
+    fn test_resolve_wikilinks() {
+        let mut index = SiteIndex::default();
+        index.insert(
+            Utf8Path::new("blog/welcome_to_my_blog.md"),
+            "blog/welcome-to-my-blog/",
+            Some("Welcome!"),
+        );
+        index.insert(Utf8Path::new("about.html"), "about/", None);
+
+        let contents = "See [[welcome_to_my_blog]], [[Welcome!|the *intro*]], \
+                        [[/about#team]] and [[blog/welcome_to_my_blog.md|again]].";
+        let mut blocks = parse_blocks(contents);
+        let mut resolved = vec![];
+        let rel_dir = Utf8Path::new("blog");
+        resolve_wikilinks(contents, &mut blocks, &index, rel_dir, &mut resolved).unwrap();
+        let links = blocks[0]
+            .tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Block(block) => Some(block),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let hrefs = links
+            .iter()
+            .map(|link| link.meta.as_ref().unwrap()["href"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hrefs,
+            vec![
+                "/blog/welcome-to-my-blog/",
+                "/blog/welcome-to-my-blog/",
+                "/about/#team",
+                "/blog/welcome-to-my-blog/",
+            ],
+        );
+        assert!(links.iter().all(|link| link.kind == "a"));
+        assert_eq!(
+            links[0].tokens,
+            vec![Token::Literal("Welcome!".to_string())]
+        );
+        assert_eq!(links[1].tokens.len(), 2);
+        assert_eq!(links[2].tokens, vec![Token::Literal("/about".to_string())]);
+
+        assert_eq!(resolved.len(), 4);
+        assert_eq!(resolved[0].text.as_deref(), Some("Welcome!"));
+        assert_eq!(resolved[1].text, None);
+        assert!(resolved
+            .iter()
+            .all(|link| index.resolves_same(link, rel_dir)));
+        // Retitling the page only affects the link that took its title.
+        let mut retitled = SiteIndex::default();
+        retitled.insert(
+            Utf8Path::new("blog/welcome_to_my_blog.md"),
+            "blog/welcome-to-my-blog/",
+            Some("Hello!"),
+        );
+        assert!(!retitled.resolves_same(&resolved[0], rel_dir));
+        assert!(!retitled.resolves_same(&resolved[1], rel_dir));
+        assert!(retitled.resolves_same(&resolved[3], rel_dir));
+        assert!(!retitled.resolves_same(&resolved[2], rel_dir));
+
+        let contents = "Fine.\n\n> A [[missing page|link]].";
+        let mut blocks = parse_blocks(contents);
+        let error = resolve_wikilinks(
+            contents,
+            &mut blocks,
+            &index,
+            Utf8Path::new(""),
+            &mut vec![],
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "3:5: unresolved link to `missing page`");
+    }
+}
@@ -23,16 +23,15 @@ type ProjectResult<T> = Result<T, ProjectError>;
 // and then the globals being constructed for use in the template.
 struct PageMeta {
     title: String,
-    // TODO we should make timezone variable so users see the timezone
-    // their machine has, instead of the less direct UTCized time.
-    timestamp: DateTime<Utc>,
+    // In the timezone of the machine, which is kept for display.
+    timestamp: DateTime<FixedOffset>,
 }
 
 impl PageMeta {
     fn new(title: String) -> Self {
         Self {
             title,
-            timestamp: Utc::now(),
+            timestamp: Local::now().fixed_offset(),
         }
     }
 
@@ -0,0 +1,104 @@
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+/// The file name of the manifest of every redirect, for hosts that understand one.
+pub const MANIFEST_FILENAME: &str = "_redirects";
+
+/// A former url of a page, to redirect to where the page lives now.
+#[derive(Debug)]
+pub struct Alias {
+    // Path of the page declaring the alias, relative to the pages directory.
+    pub page: Utf8PathBuf,
+    // Absolute url paths, like `/2019/some-post/`.
+    pub from: String,
+    pub to: String,
+}
+
+impl Alias {
+    /// The alias `from` of the page at `page`, which is served at the relative `url_path`.
+    pub fn new(page: &Utf8Path, from: &str, url_path: &str) -> Self {
+        Self {
+            page: page.to_owned(),
+            from: format!("/{}", from.trim_start_matches('/')),
+            to: format!("/{}", url_path.trim_start_matches('/')),
+        }
+    }
+
+    /// Where the redirect page goes, relative to the output directory, unless the alias isn't a
+    /// plain path, e.g. one with `..` segments that could lead outside of it.
+    pub fn out_path(&self) -> Option<Utf8PathBuf> {
+        let path = Utf8Path::new(self.from.trim_matches('/'));
+        if !path
+            .components()
+            .all(|component| matches!(component, Utf8Component::Normal(_)))
+        {
+            return None;
+        }
+        if path.extension() == Some("html") {
+            Some(path.to_owned())
+        } else {
+            Some(path.join("index.html"))
+        }
+    }
+
+    /// A page that sends browsers on to the new url, and search engines along with them.
+    pub fn redirect_page(&self) -> String {
+        let to = escape(&self.to);
+        format!(
+            "<!DOCTYPE html>\n\
+             <html lang=\"en\">\n\
+             <head>\n\
+             <meta charset=\"UTF-8\">\n\
+             <title>Redirecting to {to}</title>\n\
+             <link rel=\"canonical\" href=\"{to}\">\n\
+             <meta http-equiv=\"refresh\" content=\"0; url={to}\">\n\
+             </head>\n\
+             <body>\n\
+             <a href=\"{to}\">Moved to {to}</a>\n\
+             </body>\n\
+             </html>\n"
+        )
+    }
+}
+
+/// A `_redirects` manifest of permanent redirects, one per line.
+pub fn manifest(aliases: &[Alias]) -> String {
+    aliases
+        .iter()
+        .map(|alias| format!("{} {} 301\n", alias.from, alias.to))
+        .collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias() {
+        let alias = Alias::new(
+            Utf8Path::new("blog/post.md"),
+            "2019/some-post/",
+            "blog/post/",
+        );
+        assert_eq!(alias.from, "/2019/some-post/");
+        assert_eq!(alias.out_path().unwrap(), "2019/some-post/index.html");
+        let page = alias.redirect_page();
+        assert!(page.contains("<link rel=\"canonical\" href=\"/blog/post/\">"));
+        assert!(page.contains("<meta http-equiv=\"refresh\" content=\"0; url=/blog/post/\">"));
+
+        let alias = Alias::new(Utf8Path::new("index.md"), "/old.html", "/");
+        assert_eq!(alias.out_path().unwrap(), "old.html");
+        assert_eq!(manifest(&[alias]), "/old.html / 301\n");
+
+        for from in ["../../x/", "/blog/../../x/", "./old/"] {
+            let alias = Alias::new(Utf8Path::new("index.md"), from, "/");
+            assert_eq!(alias.out_path(), None, "{}", from);
+        }
+    }
+}
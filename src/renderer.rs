@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::ops::Deref;
 
@@ -10,11 +10,15 @@ use liquid::{ObjectView, Parser, ParserBuilder, Template, ValueView};
 use serde::Serialize;
 use thiserror::Error;
 
-use crate::common::{Block, BlockRules, ContentFile, PageData, RenderRules};
+use crate::common::{
+    Block, BlockRules, ContentFile, PageData, PageIndex, RenderRules, Taxonomy, Term,
+};
+use crate::utils::slugify;
 use crate::{diskio, Config, Markdown};
 
 pub const BLOCK_RULES_TEMPLATE_VAR: &str = "__block_rules";
 pub const STATIC_ASSET_MAP_TEMPLATE_VAR: &str = "__static_asset_map";
+pub const TIMEZONE_TEMPLATE_VAR: &str = "__timezone";
 
 // TODO not sure I necessarily want this specific impl...
 type Partials = EagerCompiler<InMemorySource>;
@@ -23,9 +27,11 @@ type Partials = EagerCompiler<InMemorySource>;
 pub enum RenderError {
     #[error("templating engine failure")]
     LiquidError(#[from] liquid::Error),
+    #[error("could not locate layout: {0}")]
+    MissingLayout(String),
 }
 
-type RenderResult<T> = Result<T, RenderError>;
+pub(crate) type RenderResult<T> = Result<T, RenderError>;
 
 fn normalize_partial_name(partial_path: &Utf8Path, current_dir: &Utf8Path) -> String {
     partial_path
@@ -69,36 +75,43 @@ fn get_inner_content(
     }
 }
 
-fn get_meta_context(page_data: &PageData) -> liquid::Object {
+fn get_meta_context(
+    renderer: &Renderer,
+    render_rules: &RenderRules,
+    page_data: &PageData,
+) -> liquid::Object {
     match page_data {
         PageData::Markdown(md) => {
-            liquid::object!({
+            // Any other frontmatter goes alongside, without shadowing the fields we fill in.
+            let mut meta = liquid::to_object(&md.frontmatter.extra).unwrap();
+            meta.extend(liquid::object!({
                 "title": md.frontmatter.title.clone(),
                 "timestamp": md.frontmatter.timestamp.to_rfc3339(),
-            })
+                "updated": md.frontmatter.updated.map(|updated| updated.to_rfc3339()),
+                "footnotes": md.footnotes,
+                "tags": TermLink::all(Taxonomy::Tags, &md.frontmatter.tags),
+                "categories": TermLink::all(Taxonomy::Categories, &md.frontmatter.categories),
+            }));
+            meta
         }
         PageData::Listing(group_path, group, page_index) => {
-            let entries: Vec<ListingEntry> = group.iter().map(|markdown| markdown.into()).collect();
-
-            let prev_page_link = if page_index.0 == 0 {
-                None
-            } else {
-                Some(format!("/{}/{}/", group_path, page_index.0 - 1))
-            };
-            let next_page_link = if (page_index.0 + 1) == page_index.1 {
-                None
-            } else {
-                Some(format!("/{}/{}/", group_path, page_index.0 + 1))
-            };
-
-            liquid::object!({
-                "title": "",
-                "timestamp": "",
-                "entries": entries,
-                "prev_page_link": prev_page_link,
-                "next_page_link": next_page_link,
-            })
+            get_listing_context(renderer, render_rules, group_path, group, page_index)
+        }
+        PageData::TermListing(term, group, page_index) => {
+            let group_path = term.taxonomy.term_url_path(&term.slug);
+            let mut meta =
+                get_listing_context(renderer, render_rules, &group_path, group, page_index);
+            meta.extend(liquid::object!({
+                "title": term.name.clone(),
+                "term": TermEntry::new(term),
+            }));
+            meta
         }
+        PageData::Terms(taxonomy, terms) => liquid::object!({
+            "title": taxonomy.name(),
+            "timestamp": "",
+            "terms": terms.iter().map(TermEntry::new).collect::<Vec<_>>(),
+        }),
         _ => liquid::object!({
             "title": "",
             "timestamp": "",
@@ -106,31 +119,124 @@ fn get_meta_context(page_data: &PageData) -> liquid::Object {
     }
 }
 
+fn get_listing_context(
+    renderer: &Renderer,
+    render_rules: &RenderRules,
+    group_path: &str,
+    group: &[(Markdown, String)],
+    page_index: &PageIndex,
+) -> liquid::Object {
+    let entries: Vec<ListingEntry> = group
+        .iter()
+        .map(|entry| ListingEntry::new(renderer, render_rules, entry))
+        .collect();
+
+    let prev_page_link = if page_index.0 == 0 {
+        None
+    } else {
+        Some(format!("/{}/{}/", group_path, page_index.0 - 1))
+    };
+    let next_page_link = if (page_index.0 + 1) == page_index.1 {
+        None
+    } else {
+        Some(format!("/{}/{}/", group_path, page_index.0 + 1))
+    };
+
+    liquid::object!({
+        "title": "",
+        "timestamp": "",
+        "entries": entries,
+        "prev_page_link": prev_page_link,
+        "next_page_link": next_page_link,
+    })
+}
+
 #[derive(ObjectView, ValueView, Clone, Debug, Serialize)]
 struct MarkdownContext {
     title: String,
     timestamp: String, // TODO can we make it so DateTime can derive a ValueView?
 }
 
+/// A term a page is filed under, linking to the listing of the term.
+#[derive(Serialize)]
+struct TermLink {
+    name: String,
+    link: String,
+}
+
+impl TermLink {
+    fn all(taxonomy: Taxonomy, terms: &[String]) -> Vec<Self> {
+        terms
+            .iter()
+            .map(|term| Self {
+                name: term.clone(),
+                link: format!("/{}/", taxonomy.term_url_path(&slugify(term))),
+            })
+            .collect()
+    }
+}
+
+/// An entry of the index of every term of a taxonomy.
+#[derive(Serialize)]
+struct TermEntry {
+    name: String,
+    link: String,
+    count: u32,
+}
+
+impl TermEntry {
+    fn new(term: &Term) -> Self {
+        Self {
+            name: term.name.clone(),
+            link: format!("/{}/", term.taxonomy.term_url_path(&term.slug)),
+            count: term.count,
+        }
+    }
+}
+
+// Frontmatter keys that would clash with the fields of a `ListingEntry`.
+const LISTING_ENTRY_FIELDS: [&str; 3] = ["link", "blocks", "summary_html"];
+
 // TODO maybe should just use this for the "page" type in the detail version too?
 #[derive(Serialize)]
 struct ListingEntry {
     title: String,
-    pub timestamp: DateTime<Utc>,
+    pub timestamp: DateTime<FixedOffset>,
+    pub updated: Option<DateTime<FixedOffset>>,
     pub slug: String,
     pub link: String, // TODO this should be "on-demand" and probably like a tag or something
     pub blocks: Vec<Block>,
+    pub summary: Vec<Block>,
+    // The `summary`, rendered with the block rules of the listing.
+    pub summary_html: String,
+    pub tags: Vec<TermLink>,
+    pub categories: Vec<TermLink>,
+    // Any other frontmatter of the entry, under its own key, e.g. `entry.description`.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
-impl From<&(Markdown, String)> for ListingEntry {
-    fn from(tup: &(Markdown, String)) -> Self {
-        let (markdown, url) = tup;
+impl ListingEntry {
+    fn new(renderer: &Renderer, render_rules: &RenderRules, entry: &(Markdown, String)) -> Self {
+        let (markdown, url) = entry;
         Self {
             title: markdown.frontmatter.title.clone(),
             timestamp: markdown.frontmatter.timestamp,
+            updated: markdown.frontmatter.updated,
             slug: markdown.frontmatter.slug.clone(),
             link: format!("/{}", url),
             blocks: markdown.blocks.clone(),
+            summary: markdown.summary.clone(),
+            summary_html: renderer.render_blocks(&markdown.summary, &render_rules.block_rules),
+            tags: TermLink::all(Taxonomy::Tags, &markdown.frontmatter.tags),
+            categories: TermLink::all(Taxonomy::Categories, &markdown.frontmatter.categories),
+            extra: markdown
+                .frontmatter
+                .extra
+                .iter()
+                .filter(|(key, _)| !LISTING_ENTRY_FIELDS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
         }
     }
 }
@@ -140,6 +246,7 @@ pub struct Renderer {
     layouts: HashMap<String, Template>,
     block_content_template: Template,
     static_asset_map: HashMap<String, String>,
+    partial_names: HashSet<String>,
 }
 
 impl Renderer {
@@ -148,14 +255,14 @@ impl Renderer {
         static_asset_map: HashMap<String, String>,
         partials: Vec<ContentFile>,
     ) -> Self {
+        let mut partial_names = HashSet::new();
         let partials =
             partials
                 .into_iter()
                 .fold(Partials::empty(), |mut partials, content_file| {
-                    partials.add(
-                        normalize_partial_name(&content_file.abs_path, &config.project_dir),
-                        content_file.content.unwrap().inner,
-                    );
+                    let name = normalize_partial_name(&content_file.abs_path, &config.project_dir);
+                    partial_names.insert(name.clone());
+                    partials.add(name, content_file.content.unwrap().inner);
                     partials
                 });
 
@@ -166,6 +273,7 @@ impl Renderer {
             .tag(crate::liquid::tags::StaticAssetTag)
             .tag(crate::liquid::tags::TailwindTag)
             .filter(crate::liquid::filters::FirstBlockOfKind)
+            .filter(crate::liquid::filters::Date)
             .build()
             .unwrap();
 
@@ -183,14 +291,19 @@ impl Renderer {
             layouts,
             block_content_template,
             static_asset_map,
+            partial_names,
         }
     }
 
-    fn get_template(&self, template_name: &str) -> &Template {
+    /// Whether there's a partial template by the `name`, like `blocks/p.liquid`.
+    pub fn has_partial(&self, name: &str) -> bool {
+        self.partial_names.contains(name)
+    }
+
+    fn get_template(&self, template_name: &str) -> RenderResult<&Template> {
         self.layouts
             .get(template_name)
-            // TODO `Result`ize me.
-            .unwrap_or_else(|| panic!("could not locate layout: {}", template_name))
+            .ok_or_else(|| RenderError::MissingLayout(template_name.to_string()))
     }
 
     // Recursively render liquid templates, allowing specification of nested layouts.
@@ -202,16 +315,17 @@ impl Renderer {
     ) -> RenderResult<String> {
         let mut content = get_inner_content(self, render_rules, page_data);
 
-        let meta_context = get_meta_context(page_data);
+        let meta_context = get_meta_context(self, render_rules, page_data);
 
         for layout in layouts.iter().rev() {
-            let template = self.get_template(layout);
+            let template = self.get_template(layout)?;
             let globals = liquid::object!({
                 // Kind of stupid to be cloning this stuff, but whatever.
                 "meta": meta_context.clone(),
                 "content": content,
                 BLOCK_RULES_TEMPLATE_VAR: render_rules.block_rules,
                 STATIC_ASSET_MAP_TEMPLATE_VAR: self.static_asset_map,
+                TIMEZONE_TEMPLATE_VAR: render_rules.timezone,
             });
             // TODO better not to discard the info from here
             content = template.render(&globals)?;
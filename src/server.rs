@@ -77,7 +77,9 @@ async fn watch(config: Arc<Config>, notify: Arc<Notify>) -> notify::Result<()> {
                 // TODO should just use events as an input instead of collecting everything.
                 if should_regenerate {
                     tracing::info!("regenerating...");
-                    crate::generate(config).await.unwrap();
+                    if let Err(e) = crate::generate(config).await {
+                        tracing::error!("{}", e);
+                    }
                     notify.notify_one();
                 }
             }
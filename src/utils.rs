@@ -1,3 +1,5 @@
+use chrono::NaiveDate;
+
 pub fn slugify(s: &str) -> String {
     s.to_lowercase()
         .chars()
@@ -9,6 +11,25 @@ pub fn slugify(s: &str) -> String {
         .collect()
 }
 
+/// Escape text for use as HTML. Quotes are left alone, since text never ends up in attributes.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Split a leading `YYYY-MM-DD-` date off a file stem like `2023-11-11-my-post`, returning the
+/// date along with the rest of the stem.
+pub fn split_date_prefix(stem: &str) -> Option<(NaiveDate, &str)> {
+    let (date, rest) = (stem.get(..10)?, stem.get(10..)?);
+    let rest = rest.strip_prefix('-')?;
+    if rest.is_empty() {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((date, rest))
+}
+
 /// Hash the given `bytes`.
 pub fn hash(bytes: &[u8]) -> u64 {
     seahash::hash(bytes)
@@ -34,6 +55,17 @@ mod tests {
         assert_eq!(slugify("Hello, World!"), "hello-world");
     }
 
+    #[test]
+    fn test_split_date_prefix() {
+        assert_eq!(
+            split_date_prefix("2023-11-11-my-post"),
+            Some((NaiveDate::from_ymd_opt(2023, 11, 11).unwrap(), "my-post"))
+        );
+        assert_eq!(split_date_prefix("2023-11-11"), None);
+        assert_eq!(split_date_prefix("2023-13-11-my-post"), None);
+        assert_eq!(split_date_prefix("my-post"), None);
+    }
+
     #[test]
     fn test_divide_round_up() {
         assert_eq!(divide_round_up(5, 2), 3);
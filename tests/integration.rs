@@ -29,6 +29,12 @@ async fn generate_example_site() {
         "blog/0/index.html",
         "blog/welcome-to-my-blog/index.html",
         "index.html",
+        // The first page of each term's listing also goes at the url of the term itself.
+        "tags/index.html",
+        "tags/meta/0/index.html",
+        "tags/meta/index.html",
+        "tags/stalagmite/0/index.html",
+        "tags/stalagmite/index.html",
     ];
     assert_eq!(files, expected_files);
 